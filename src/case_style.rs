@@ -0,0 +1,83 @@
+use derive_more::Display;
+use inflector::Inflector;
+use serde::Deserialize;
+
+/// Spellings of an identify recognized by `rename`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum CaseStyle {
+    #[display(fmt = "pascal")]
+    Pascal,
+    #[display(fmt = "camel")]
+    Camel,
+    #[display(fmt = "kebab")]
+    Kebab,
+    #[display(fmt = "snake")]
+    Snake,
+    #[display(fmt = "screaming_snake")]
+    ScreamingSnake,
+    #[display(fmt = "train")]
+    Train,
+    #[display(fmt = "title")]
+    Title,
+    #[display(fmt = "sentence")]
+    Sentence,
+    #[display(fmt = "dot")]
+    Dot,
+    #[display(fmt = "path")]
+    Path,
+}
+
+impl CaseStyle {
+    /// All styles in the priority order used when two styles spell an identify the same way.
+    pub fn all() -> Vec<CaseStyle> {
+        vec![
+            CaseStyle::Pascal,
+            CaseStyle::Camel,
+            CaseStyle::Kebab,
+            CaseStyle::Snake,
+            CaseStyle::ScreamingSnake,
+            CaseStyle::Train,
+            CaseStyle::Title,
+            CaseStyle::Sentence,
+            CaseStyle::Dot,
+            CaseStyle::Path,
+        ]
+    }
+
+    pub fn convert(&self, value: &str) -> String {
+        match self {
+            CaseStyle::Pascal => value.to_pascal_case(),
+            CaseStyle::Camel => value.to_camel_case(),
+            CaseStyle::Kebab => value.to_kebab_case(),
+            CaseStyle::Snake => value.to_snake_case(),
+            CaseStyle::ScreamingSnake => value.to_screaming_snake_case(),
+            CaseStyle::Train => value.to_train_case(),
+            CaseStyle::Title => value.to_title_case(),
+            CaseStyle::Sentence => value.to_sentence_case(),
+            CaseStyle::Dot => value.to_snake_case().replace('_', "."),
+            CaseStyle::Path => value.to_snake_case().replace('_', "/"),
+        }
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg(test)]
+mod tests {
+    use super::CaseStyle;
+
+    #[test]
+    fn case_style_convert() {
+        let actual = CaseStyle::all()
+            .iter()
+            .map(|style| style.convert("fooBar"))
+            .collect::<Vec<String>>();
+        assert_eq!(
+            actual,
+            vec![
+                "FooBar", "fooBar", "foo-bar", "foo_bar", "FOO_BAR", "Foo-Bar", "Foo Bar",
+                "Foo bar", "foo.bar", "foo/bar"
+            ]
+        );
+    }
+}
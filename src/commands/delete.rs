@@ -1,24 +1,52 @@
-use crate::delete_executor::{DeleteExecutor, FSDeleteExecutor, FSDeleteExecutorDeps};
-use crate::markdown::parse;
-use crate::template::{render, MdmgCtx};
+use crate::delete_executor::{
+    DeleteExecutor, DryRunDeleteExecutor, FSDeleteExecutor, FSDeleteExecutorDeps,
+    RemovableDirectories,
+};
+use crate::error::MdmgError;
+use crate::front_matter::FrontMatter;
+use crate::journal::{
+    FSJournalRepository, JournalEntry, JournalFile, JournalOperation, JournalRepository,
+};
+use crate::plan::{render_plan, RenderedPlan};
+use crate::sandbox::Sandbox;
+use crate::scaffold::Scaffold;
+use crate::template::MdmgCtx;
 use crate::template_repository::{FSTemplateRepository, TemplateRepository};
+use crate::transaction::{atomically, FSTransaction, Transaction};
+use crate::variable::Variables;
 use crate::Result;
 
-use std::env::current_dir;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use diffy::{create_patch, PatchFormatter};
+use yansi::Paint;
+
 pub trait DeleteCommand {
-    fn run(&self, plan_name: String, component_name: String) -> Result<()>;
+    fn run(
+        &self,
+        plan_name: String,
+        component_name: String,
+        variables: Variables,
+        dry_run: bool,
+        force: bool,
+        strict: bool,
+    ) -> Result<()>;
 }
 
 pub struct DeleteCommandImpl {
     template_repository_ref: Arc<dyn TemplateRepository>,
     delete_executor_ref: Arc<dyn DeleteExecutor>,
+    transaction_ref: Arc<dyn Transaction>,
+    journal_repository_ref: Arc<dyn JournalRepository>,
 }
 
 trait Dependencies {
     fn template_repository(&self) -> Arc<dyn TemplateRepository>;
     fn delete_executor(&self) -> Arc<dyn DeleteExecutor>;
+    fn transaction(&self) -> Arc<dyn Transaction>;
+    fn journal_repository(&self) -> Arc<dyn JournalRepository>;
 }
 
 impl Dependencies for DeleteCommandImpl {
@@ -29,57 +57,239 @@ impl Dependencies for DeleteCommandImpl {
     fn delete_executor(&self) -> Arc<dyn DeleteExecutor> {
         self.delete_executor_ref.clone()
     }
+
+    fn transaction(&self) -> Arc<dyn Transaction> {
+        self.transaction_ref.clone()
+    }
+
+    fn journal_repository(&self) -> Arc<dyn JournalRepository> {
+        self.journal_repository_ref.clone()
+    }
 }
 
 #[cfg(not(tarpaulin_include))]
 impl DeleteCommandImpl {
-    pub fn new() -> Self {
-        let current_dir = current_dir().expect("failed fetch current dir");
-        let delete_executor_deps = Arc::new(FSDeleteExecutorDeps::new());
+    // The executors keep their state in `RefCell`, and the command never crosses threads.
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn new(root: PathBuf, allow_outside_root: bool) -> Self {
+        let transaction = Arc::new(FSTransaction::new());
+        let delete_executor_deps = Arc::new(FSDeleteExecutorDeps::new(
+            transaction.clone(),
+            Sandbox::new(root.clone(), allow_outside_root),
+        ));
 
         DeleteCommandImpl {
-            template_repository_ref: Arc::new(FSTemplateRepository::new(current_dir.join(".mdmg"))),
+            template_repository_ref: Arc::new(FSTemplateRepository::new(root.join(".mdmg"))),
             delete_executor_ref: Arc::new(FSDeleteExecutor::new(delete_executor_deps)),
+            transaction_ref: transaction,
+            journal_repository_ref: Arc::new(FSJournalRepository::new(
+                root.join(".mdmg").join(".journal.json"),
+                root.join(".mdmg").join(".objects"),
+            )),
         }
     }
 }
 
-impl DeleteCommand for DeleteCommandImpl {
-    fn run(&self, plan_name: String, component_name: String) -> Result<()> {
-        let template = self.template_repository().resolve(plan_name)?;
-        let render_ctx = MdmgCtx::new(component_name);
-        let scaffolds = parse(render(template, &render_ctx)?)?;
-
-        for scaffold in scaffolds.into_iter() {
-            match &self.delete_executor().execute(&scaffold) {
-                Ok(_) => {}
-                Err(e) => eprintln!("{}", e),
+impl DeleteCommandImpl {
+    /// Resolve the files of the component from the journal, and fall back to rendering the plan.
+    /// Only the directories recorded in the journal are removed, otherwise any directory under the project root.
+    fn resolve_plan(
+        &self,
+        plan_name: &str,
+        component_name: String,
+        variables: Variables,
+        strict: bool,
+    ) -> Result<(RenderedPlan, RemovableDirectories)> {
+        let journal = self.journal_repository().load()?;
+        match journal.find_component(plan_name, &component_name) {
+            Some(entry) => Ok((
+                RenderedPlan {
+                    plan_hash: entry.plan_hash.clone(),
+                    front_matter: FrontMatter::default(),
+                    ctx: MdmgCtx::new(entry.identify.clone())
+                        .with_variables(entry.variables.clone()),
+                    scaffolds: entry
+                        .files
+                        .iter()
+                        .filter_map(|file| file.hash.as_ref().map(|hash| (file, hash)))
+                        .map(|(file, hash)| {
+                            let body = self.journal_repository().resolve_object(hash)?;
+                            Ok(Scaffold::Complete {
+                                file_name: file.path.clone(),
+                                file_body: String::from_utf8_lossy(&body).to_string(),
+                            })
+                        })
+                        .collect::<Result<Vec<Scaffold>>>()?,
+                },
+                RemovableDirectories::Only(entry.directories.iter().map(PathBuf::from).collect()),
+            )),
+            None => Ok((
+                render_plan(
+                    self.template_repository().as_ref(),
+                    plan_name,
+                    MdmgCtx::new(component_name)
+                        .with_variables(variables)
+                        .with_strict(strict),
+                )?,
+                RemovableDirectories::UnderRoot,
+            )),
+        }
+    }
+}
+
+/// Print the diff of the files whose content differs from the generated body, and return their names.
+fn check_modified_files(scaffolds: &[Scaffold]) -> Vec<String> {
+    let mut modified_files = vec![];
+    for scaffold in scaffolds.iter() {
+        if let Scaffold::Complete {
+            file_name,
+            file_body,
+        } = scaffold
+        {
+            match read_to_string(file_name) {
+                Ok(current) if &current != file_body => {
+                    println!(
+                        "{} {}",
+                        Paint::yellow("Modified since generation:"),
+                        file_name
+                    );
+                    println!(
+                        "{}",
+                        PatchFormatter::new()
+                            .with_color()
+                            .fmt_patch(&create_patch(file_body, &current))
+                    );
+                    modified_files.push(file_name.clone());
+                }
+                _ => {}
             }
         }
+    }
+    modified_files
+}
+
+impl DeleteCommand for DeleteCommandImpl {
+    fn run(
+        &self,
+        plan_name: String,
+        component_name: String,
+        variables: Variables,
+        dry_run: bool,
+        force: bool,
+        strict: bool,
+    ) -> Result<()> {
+        let (
+            RenderedPlan {
+                plan_hash,
+                ctx,
+                scaffolds,
+                ..
+            },
+            removable_directories,
+        ) = self.resolve_plan(&plan_name, component_name, variables, strict)?;
+
+        let modified_files = check_modified_files(&scaffolds);
+        if dry_run {
+            let executor = DryRunDeleteExecutor::default();
+            return scaffolds
+                .iter()
+                .try_for_each(|scaffold| executor.execute(scaffold, &removable_directories));
+        }
+        if !modified_files.is_empty() && !force {
+            return Err(MdmgError::LocallyModifiedFiles(modified_files));
+        }
+
+        // The deleted contents and the journal entry are written in the transaction, so nothing is
+        // left behind when a step fails.
+        let transaction = self.transaction();
+        atomically(transaction.as_ref(), || {
+            let mut deleted_files = vec![];
+            for scaffold in scaffolds.iter() {
+                let previous_hash = self
+                    .journal_repository()
+                    .save_file(transaction.as_ref(), Path::new(scaffold.file_name()))?;
+                if previous_hash.is_some() {
+                    deleted_files.push(JournalFile {
+                        path: scaffold.file_name().to_string(),
+                        previous_hash,
+                        ..JournalFile::default()
+                    });
+                }
+            }
+
+            scaffolds.iter().try_for_each(|scaffold| {
+                self.delete_executor()
+                    .execute(scaffold, &removable_directories)
+            })?;
 
-        Ok(())
+            if !deleted_files.is_empty() {
+                self.journal_repository().append(JournalEntry::new(
+                    JournalOperation::Delete,
+                    plan_name,
+                    plan_hash,
+                    ctx.identify,
+                    ctx.variables,
+                    deleted_files,
+                ))?;
+            }
+            Ok(())
+        })
     }
 }
 
 #[cfg(not(tarpaulin_include))]
 #[cfg(test)]
+#[allow(clippy::arc_with_non_send_sync)]
 mod tests {
     use crate::{
-        commands::delete::DeleteCommand, delete_executor::DeleteExecutor, file::FileName,
+        commands::delete::DeleteCommand,
+        delete_executor::{DeleteExecutor, RemovableDirectories},
+        file::FileName,
+        journal::{hash, Journal, JournalEntry, JournalRepository},
         template_repository::TemplateRepository,
+        transaction::{FSTransaction, Transaction},
+        variable::Variables,
     };
 
-    use super::DeleteCommandImpl;
+    use super::{check_modified_files, DeleteCommandImpl};
 
+    use crate::scaffold::Scaffold;
     use indoc::indoc;
     use std::cell::RefCell;
+    use std::fs::{create_dir_all, remove_dir_all, write};
     use std::sync::Arc;
 
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    pub fn check_modified_files_returning_files_differ_from_generated_body() {
+        let base = "./support/check_modified_files_returning_files_differ_from_generated_body";
+        assert!(create_dir_all(base).is_ok());
+        assert!(write(format!("{}/modified.md", base), "modified").is_ok());
+        assert!(write(format!("{}/clean.md", base), "dummy").is_ok());
+
+        let scaffolds = ["modified.md", "clean.md", "not_found.md"]
+            .iter()
+            .map(|file_name| Scaffold::Complete {
+                file_name: format!("{}/{}", base, file_name),
+                file_body: "dummy".to_string(),
+            })
+            .collect::<Vec<Scaffold>>();
+
+        assert_eq!(
+            check_modified_files(&scaffolds),
+            vec![format!("{}/modified.md", base)]
+        );
+        remove_dir_all(base).unwrap();
+    }
+
     #[test]
     pub fn success_delete_command() {
         #[derive(Default)]
         struct StubTemplateRepository {}
 
+        #[derive(Default)]
+        struct StubJournalRepository {}
+
         #[derive(Default)]
         struct StubDeleteExecutor {
             pub deleted_file: RefCell<Vec<String>>,
@@ -111,7 +321,11 @@ mod tests {
         }
 
         impl DeleteExecutor for StubDeleteExecutor {
-            fn execute(&self, scaffold: &crate::scaffold::Scaffold) -> crate::Result<()> {
+            fn execute(
+                &self,
+                scaffold: &crate::scaffold::Scaffold,
+                _removable_directories: &RemovableDirectories,
+            ) -> crate::Result<()> {
                 match scaffold {
                     crate::scaffold::Scaffold::Complete {
                         file_name,
@@ -125,19 +339,44 @@ mod tests {
             }
         }
 
-        let stub_delete_executor_ref = Arc::new(StubDeleteExecutor::default());
-
-        impl DeleteCommandImpl {
-            fn dummy_new(stub_delete_executor_ref: Arc<StubDeleteExecutor>) -> Self {
-                DeleteCommandImpl {
-                    template_repository_ref: Arc::new(StubTemplateRepository::default()),
-                    delete_executor_ref: stub_delete_executor_ref,
-                }
+        impl JournalRepository for StubJournalRepository {
+            fn load(&self) -> crate::Result<Journal> {
+                Ok(Journal::default())
+            }
+            fn append(&self, entry: JournalEntry) -> crate::Result<JournalEntry> {
+                Ok(entry)
+            }
+            fn remove(&self, _id: u64) -> crate::Result<()> {
+                Ok(())
+            }
+            fn save_object(
+                &self,
+                _transaction: &dyn Transaction,
+                body: &[u8],
+            ) -> crate::Result<String> {
+                Ok(hash(body))
+            }
+            fn resolve_object(&self, _hash: &str) -> crate::Result<Vec<u8>> {
+                unimplemented!()
             }
         }
 
-        let delete_command = DeleteCommandImpl::dummy_new(stub_delete_executor_ref.clone());
-        let actual = delete_command.run("dummy".to_string(), "dummy".to_string());
+        let stub_delete_executor_ref = Arc::new(StubDeleteExecutor::default());
+
+        let delete_command = DeleteCommandImpl {
+            template_repository_ref: Arc::new(StubTemplateRepository::default()),
+            delete_executor_ref: stub_delete_executor_ref.clone(),
+            transaction_ref: Arc::new(FSTransaction::new()),
+            journal_repository_ref: Arc::new(StubJournalRepository::default()),
+        };
+        let actual = delete_command.run(
+            "dummy".to_string(),
+            "dummy".to_string(),
+            Variables::new(),
+            false,
+            false,
+            true,
+        );
 
         assert!(actual.is_ok());
         assert_eq!(
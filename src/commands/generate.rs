@@ -1,54 +1,238 @@
-use crate::markdown::parse;
-use crate::scaffold_executor::{DryRunScaffoldExecutor, FSScaffoldExecutor, ScaffoldExecutor};
-use crate::template::{render, MdmgCtx};
+use crate::error::MdmgError;
+use crate::journal::{
+    FSJournalRepository, JournalEntry, JournalFile, JournalOperation, JournalRepository,
+};
+use crate::plan::{render_plan, RenderedPlan};
+use crate::sandbox::Sandbox;
+use crate::scaffold::Scaffold;
+use crate::scaffold_executor::{
+    backup_path, validate_destination, ConflictStrategy, DryRunScaffoldExecutor, ExecutionResult,
+    FSScaffoldExecutor, ScaffoldExecutor,
+};
+use crate::snapshot_repository::{FSSnapshotRepository, SnapshotRepository};
+use crate::template::MdmgCtx;
 use crate::template_repository::{FSTemplateRepository, TemplateRepository};
+use crate::transaction::{atomically, FSTransaction, Transaction};
+use crate::variable::Variables;
 use crate::Result;
 
-use std::env::current_dir;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-pub struct GenerateCommandImpl;
+pub struct GenerateCommandImpl {
+    root: PathBuf,
+    sandbox: Sandbox,
+}
 
 impl GenerateCommandImpl {
-    pub fn new() -> Self {
-        GenerateCommandImpl
+    pub fn new(root: PathBuf, allow_outside_root: bool) -> Self {
+        GenerateCommandImpl {
+            sandbox: Sandbox::new(root.clone(), allow_outside_root),
+            root,
+        }
     }
 }
 
 trait Dependencies {
     fn template_repository(&self) -> Arc<dyn TemplateRepository>;
+    fn snapshot_repository(&self) -> Arc<dyn SnapshotRepository>;
+    fn transaction(&self) -> Arc<dyn Transaction>;
+    fn journal_repository(&self) -> Arc<dyn JournalRepository>;
 }
 
 impl Dependencies for GenerateCommandImpl {
     fn template_repository(&self) -> Arc<dyn TemplateRepository> {
-        let current_dir = current_dir().expect("failed fetch current dir");
-        Arc::new(FSTemplateRepository::new(current_dir.join(".mdmg")))
+        Arc::new(FSTemplateRepository::new(self.root.join(".mdmg")))
+    }
+    fn snapshot_repository(&self) -> Arc<dyn SnapshotRepository> {
+        Arc::new(FSSnapshotRepository::new(
+            self.root.join(".mdmg").join(".snapshots"),
+        ))
+    }
+    fn transaction(&self) -> Arc<dyn Transaction> {
+        Arc::new(FSTransaction::new())
+    }
+    fn journal_repository(&self) -> Arc<dyn JournalRepository> {
+        Arc::new(FSJournalRepository::new(
+            self.root.join(".mdmg").join(".journal.json"),
+            self.root.join(".mdmg").join(".objects"),
+        ))
     }
 }
 
 pub trait GenerateCommand {
-    fn run(&self, plan_name: String, component_name: String, dry_run: bool) -> Result<()>;
+    fn run(
+        &self,
+        plan_name: String,
+        component_name: String,
+        variables: Variables,
+        dry_run: bool,
+        on_conflict: ConflictStrategy,
+        strict: bool,
+    ) -> Result<()>;
 }
 
 impl GenerateCommand for GenerateCommandImpl {
-    fn run(&self, plan_name: String, component_name: String, dry_run: bool) -> Result<()> {
-        let template = self.template_repository().resolve(plan_name)?;
-        let render_ctx = MdmgCtx::new(component_name);
-        if let Ok(scaffolds) = parse(render(template, &render_ctx)?) {
+    fn run(
+        &self,
+        plan_name: String,
+        component_name: String,
+        variables: Variables,
+        dry_run: bool,
+        on_conflict: ConflictStrategy,
+        strict: bool,
+    ) -> Result<()> {
+        let RenderedPlan {
+            plan_hash,
+            ctx,
+            scaffolds,
+            ..
+        } = render_plan(
+            self.template_repository().as_ref(),
+            &plan_name,
+            MdmgCtx::new(component_name)
+                .with_variables(variables)
+                .with_strict(strict),
+        )?;
+
+        if dry_run {
             for scaffold in scaffolds.iter() {
-                match dry_run {
-                    true => DryRunScaffoldExecutor::new().execute(scaffold)?,
-                    false => FSScaffoldExecutor::new().execute(scaffold)?,
-                };
+                DryRunScaffoldExecutor::new().execute(scaffold)?;
+            }
+            return Ok(());
+        }
+
+        if on_conflict == ConflictStrategy::Error {
+            let exist_files = scaffolds
+                .iter()
+                .map(|scaffold| scaffold.file_name().to_string())
+                .filter(|file_name| Path::new(file_name).exists())
+                .collect::<Vec<String>>();
+            if !exist_files.is_empty() {
+                return Err(MdmgError::ConflictingFiles(exist_files));
             }
-        };
-        Ok(())
+        }
+        for scaffold in scaffolds.iter() {
+            validate_destination(Path::new(scaffold.file_name()))?;
+        }
+
+        let missing_directories = scaffolds
+            .iter()
+            .flat_map(|scaffold| Path::new(scaffold.file_name()).ancestors().skip(1))
+            .filter(|directory| !directory.as_os_str().is_empty() && !directory.exists())
+            .map(|directory| directory.to_path_buf())
+            .collect::<BTreeSet<PathBuf>>();
+
+        let transaction = self.transaction();
+        let snapshot_repository = self.snapshot_repository();
+        let executor = FSScaffoldExecutor::new(
+            on_conflict,
+            snapshot_repository.clone(),
+            transaction.clone(),
+            self.sandbox.clone(),
+        );
+        // The replaced contents, the snapshots and the journal entry are written in the transaction,
+        // so the generated files are rolled back when they cannot be recorded.
+        let (skipped, conflicted) = atomically(transaction.as_ref(), || {
+            let previous_hashes = scaffolds
+                .iter()
+                .map(|scaffold| {
+                    self.journal_repository()
+                        .save_file(transaction.as_ref(), Path::new(scaffold.file_name()))
+                })
+                .collect::<Result<Vec<Option<String>>>>()?;
+            let results = scaffolds
+                .iter()
+                .map(|scaffold| executor.execute(scaffold))
+                .collect::<Result<Vec<ExecutionResult>>>()?;
+
+            let mut skipped = vec![];
+            let mut conflicted = vec![];
+            let mut generated_files = vec![];
+            let mut backups = vec![];
+            for ((scaffold, result), previous_hash) in
+                scaffolds.iter().zip(results).zip(previous_hashes)
+            {
+                match (scaffold, result) {
+                    (_, ExecutionResult::Skipped) => skipped.push(scaffold.file_name().to_string()),
+                    (_, ExecutionResult::Ignored) => {}
+                    (
+                        Scaffold::Complete {
+                            file_name,
+                            file_body,
+                        },
+                        _,
+                    ) => {
+                        // Keep the generated body as the base of the next merge.
+                        snapshot_repository.save(
+                            transaction.as_ref(),
+                            Path::new(file_name),
+                            file_body,
+                        )?;
+                        // The backup holds the replaced content, so undo removes it and restores the file.
+                        if result == ExecutionResult::BackedUp {
+                            backups.push(JournalFile {
+                                path: backup_path(Path::new(file_name))
+                                    .to_string_lossy()
+                                    .to_string(),
+                                hash: previous_hash.clone(),
+                                ..JournalFile::default()
+                            });
+                        }
+                        generated_files.push(JournalFile {
+                            path: file_name.clone(),
+                            hash: self
+                                .journal_repository()
+                                .save_file(transaction.as_ref(), Path::new(file_name))?,
+                            previous_hash,
+                            ..JournalFile::default()
+                        });
+                        if result == ExecutionResult::MergedWithConflicts {
+                            conflicted.push(file_name.clone());
+                        }
+                    }
+                    (Scaffold::Pending { .. }, _) => {}
+                }
+            }
+
+            if !generated_files.is_empty() {
+                let created_directories = missing_directories
+                    .iter()
+                    .filter(|directory| directory.exists())
+                    .map(|directory| directory.to_string_lossy().to_string())
+                    .collect();
+                self.journal_repository().append(
+                    JournalEntry::new(
+                        JournalOperation::Generate,
+                        plan_name,
+                        plan_hash,
+                        ctx.identify,
+                        ctx.variables,
+                        generated_files,
+                    )
+                    .with_references(backups)
+                    .with_directories(created_directories),
+                )?;
+            }
+            Ok((skipped, conflicted))
+        })?;
+
+        if skipped.is_empty() && conflicted.is_empty() {
+            Ok(())
+        } else {
+            Err(MdmgError::IncompleteGeneration {
+                skipped,
+                conflicted,
+            })
+        }
     }
 }
 
 #[cfg(not(tarpaulin_include))]
 #[cfg(test)]
 mod tests {
+    use std::env::current_dir;
     use std::fs::{remove_file, write};
     use std::path::Path;
 
@@ -79,8 +263,15 @@ mod tests {
     #[cfg_attr(not(feature = "fs-test"), ignore)]
     pub fn generate_command_run_is_file_delete() {
         setup_template();
-        let command = GenerateCommandImpl::new();
-        let actual = command.run("example".to_string(), "foo".to_string(), false);
+        let command = GenerateCommandImpl::new(current_dir().unwrap(), false);
+        let actual = command.run(
+            "example".to_string(),
+            "foo".to_string(),
+            Variables::new(),
+            false,
+            ConflictStrategy::Skip,
+            true,
+        );
 
         assert!(actual.is_ok());
         terradown_template();
@@ -1,7 +1,7 @@
 use crate::logger::{Logger, StdoutLogger};
 use crate::template_repository::{FSTemplateRepository, TemplateRepository};
 use crate::Result;
-use std::env::current_dir;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 pub trait ListCommand {
@@ -18,13 +18,10 @@ pub struct ListCommandImpl {
     template_repository_instance: Arc<dyn TemplateRepository>,
 }
 
-impl Default for ListCommandImpl {
-    fn default() -> Self {
-        let current_dir = current_dir().expect("failed fetch current dir");
+impl ListCommandImpl {
+    pub fn new(root: PathBuf) -> Self {
         ListCommandImpl {
-            template_repository_instance: Arc::new(FSTemplateRepository::new(
-                current_dir.join(".mdmg"),
-            )),
+            template_repository_instance: Arc::new(FSTemplateRepository::new(root.join(".mdmg"))),
             logger_instance: Arc::new(StdoutLogger::new()),
         }
     }
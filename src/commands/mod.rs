@@ -3,3 +3,4 @@ pub mod generate;
 pub mod list;
 pub mod rename;
 pub mod setup;
+pub mod undo;
@@ -1,44 +1,189 @@
+use crate::delete_executor::RemovableDirectories;
+use crate::error::MdmgError;
+use crate::front_matter::{self, FrontMatter, RenameOptions};
 use crate::generated_file_repository::FSGeneratedFileRepository;
+use crate::journal::{
+    FSJournalRepository, JournalEntry, JournalFile, JournalOperation, JournalRepository,
+};
 use crate::logger::{Logger, StdoutLogger};
-use crate::markdown::parse;
+use crate::plan::{render_plan, RenderedPlan};
+use crate::project_file_repository::{FSProjectFileRepository, ProjectFileRepository};
 use crate::rename_executor::{
-    DefaultRenameExecutor, FSReplacementOperationInterpreter, RenameExecutor,
+    DefaultRenameExecutor, DirectoryMove, DryRunReplacementOperationInterpreter,
+    FSReplacementOperationInterpreter, Occurrence, RenameExecutor, RenameResult,
+    ReplacementOperation, ReplacementParameter,
 };
-use crate::template::{render, MdmgCtx};
+use crate::sandbox::Sandbox;
+use crate::scaffold::Scaffold;
+use crate::template::MdmgCtx;
 use crate::template_repository::{FSTemplateRepository, TemplateRepository};
+use crate::transaction::{atomically, FSTransaction, Transaction};
+use crate::variable::Variables;
 use crate::Result;
 
-use std::env::current_dir;
+use derive_more::Display;
+use diffy::{DiffOptions, Patch, PatchFormatter};
+use ignore::WalkBuilder;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
+use yansi::Paint;
 
 pub trait RenameCommand {
-    fn run(&self, plan_name: &str, identify: &str, replaced_identify: &str) -> Result<()>;
+    fn run(
+        &self,
+        plan_name: &str,
+        identify: &str,
+        replaced_identify: &str,
+        variables: Variables,
+        flags: RenameFlags,
+    ) -> Result<()>;
+}
+
+/// Switches of `rename` given on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenameFlags {
+    pub word_boundary: bool,
+    pub case_boundary: bool,
+    pub update_references: bool,
+    pub dry_run: bool,
+    pub output: OutputFormat,
+    pub strict: bool,
+}
+
+impl Default for RenameFlags {
+    fn default() -> Self {
+        RenameFlags {
+            word_boundary: true,
+            case_boundary: false,
+            update_references: false,
+            dry_run: false,
+            output: OutputFormat::Text,
+            strict: true,
+        }
+    }
+}
+
+/// How `rename --dry-run` prints the plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum OutputFormat {
+    #[display(fmt = "text")]
+    Text,
+    #[display(fmt = "json")]
+    Json,
+}
+
+impl OutputFormat {
+    pub fn variants() -> [&'static str; 2] {
+        ["text", "json"]
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = MdmgError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(MdmgError::InvalidOutputFormat(s.to_string())),
+        }
+    }
+}
+
+/// A file in the plan printed by `rename --dry-run --output json`.
+#[derive(Debug, Serialize)]
+struct PlannedFile<'a> {
+    operation: ReplacementOperation,
+    from: &'a str,
+    to: &'a str,
+    diff: String,
+    occurrences: &'a [Occurrence],
+}
+
+impl<'a> PlannedFile<'a> {
+    fn new(parameter: &'a ReplacementParameter) -> Self {
+        PlannedFile {
+            operation: ReplacementOperation::from(parameter),
+            from: parameter.id(),
+            to: parameter.renamed_name(),
+            diff: if parameter.body_changed() {
+                diff(parameter).to_string()
+            } else {
+                String::new()
+            },
+            occurrences: parameter.occurrences(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PlannedRename<'a> {
+    directories: &'a [DirectoryMove],
+    files: Vec<PlannedFile<'a>>,
+    references: Vec<PlannedFile<'a>>,
+}
+
+fn diff(parameter: &ReplacementParameter) -> Patch<'_, str> {
+    DiffOptions::new()
+        .set_original_filename(format!("a/{}", parameter.id()))
+        .set_modified_filename(format!("b/{}", parameter.renamed_name()))
+        .create_patch(parameter.before_replace_body(), parameter.replaced_body())
 }
 
 pub struct RenameCommandImpl {
     template_repository_instance: Arc<dyn TemplateRepository>,
     logger_instance: Arc<dyn Logger>,
     rename_executor_instance: Arc<dyn RenameExecutor>,
+    dry_run_rename_executor_instance: Arc<dyn RenameExecutor>,
+    transaction_instance: Arc<dyn Transaction>,
+    journal_repository_instance: Arc<dyn JournalRepository>,
+    project_file_repository_instance: Arc<dyn ProjectFileRepository>,
 }
 
 impl RenameCommandImpl {
-    pub fn new() -> Self {
-        let current_dir = current_dir().expect("failed fetch current dir");
+    /// The plan may have been removed after generation, then the default options are used.
+    fn plan_front_matter(&self, plan_name: &str) -> Result<FrontMatter> {
+        match self.template_repository().resolve(plan_name.to_string()) {
+            Ok(template) => Ok(front_matter::extract(template)?.0),
+            Err(_) => Ok(FrontMatter::default()),
+        }
+    }
+
+    // The executors keep their state in `RefCell`, and the command never crosses threads.
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn new(root: PathBuf, allow_outside_root: bool) -> Self {
         let logger = Arc::new(StdoutLogger::new());
+        let transaction = Arc::new(FSTransaction::new());
         let replacement_operation_interpreter_instance: Arc<FSReplacementOperationInterpreter> =
-            Arc::new(FSReplacementOperationInterpreter::new(logger.clone()));
+            Arc::new(FSReplacementOperationInterpreter::new(
+                logger.clone(),
+                transaction.clone(),
+                Sandbox::new(root.clone(), allow_outside_root),
+            ));
         let generated_file_repository: Arc<FSGeneratedFileRepository> =
-            Arc::new(FSGeneratedFileRepository::new(current_dir.clone()));
+            Arc::new(FSGeneratedFileRepository::new(root.clone()));
 
         RenameCommandImpl {
-            template_repository_instance: Arc::new(FSTemplateRepository::new(
-                current_dir.join(".mdmg"),
-            )),
+            template_repository_instance: Arc::new(FSTemplateRepository::new(root.join(".mdmg"))),
             logger_instance: logger,
             rename_executor_instance: Arc::new(DefaultRenameExecutor::new(
                 replacement_operation_interpreter_instance,
+                generated_file_repository.clone(),
+            )),
+            dry_run_rename_executor_instance: Arc::new(DefaultRenameExecutor::new(
+                Arc::new(DryRunReplacementOperationInterpreter::new()),
                 generated_file_repository,
             )),
+            transaction_instance: transaction,
+            journal_repository_instance: Arc::new(FSJournalRepository::new(
+                root.join(".mdmg").join(".journal.json"),
+                root.join(".mdmg").join(".objects"),
+            )),
+            project_file_repository_instance: Arc::new(FSProjectFileRepository::new(root)),
         }
     }
 }
@@ -47,6 +192,10 @@ trait Dependencies {
     fn template_repository(&self) -> Arc<dyn TemplateRepository>;
     fn logger(&self) -> Arc<dyn Logger>;
     fn rename_executor(&self) -> Arc<dyn RenameExecutor>;
+    fn dry_run_rename_executor(&self) -> Arc<dyn RenameExecutor>;
+    fn transaction(&self) -> Arc<dyn Transaction>;
+    fn journal_repository(&self) -> Arc<dyn JournalRepository>;
+    fn project_file_repository(&self) -> Arc<dyn ProjectFileRepository>;
 }
 
 impl Dependencies for RenameCommandImpl {
@@ -59,34 +208,430 @@ impl Dependencies for RenameCommandImpl {
     fn rename_executor(&self) -> Arc<dyn RenameExecutor> {
         self.rename_executor_instance.clone()
     }
+    fn dry_run_rename_executor(&self) -> Arc<dyn RenameExecutor> {
+        self.dry_run_rename_executor_instance.clone()
+    }
+    fn transaction(&self) -> Arc<dyn Transaction> {
+        self.transaction_instance.clone()
+    }
+    fn journal_repository(&self) -> Arc<dyn JournalRepository> {
+        self.journal_repository_instance.clone()
+    }
+    fn project_file_repository(&self) -> Arc<dyn ProjectFileRepository> {
+        self.project_file_repository_instance.clone()
+    }
+}
+
+impl RenameCommandImpl {
+    /// Files outside the plan that are moved together with the directories, keyed by the new path.
+    fn moved_files(
+        &self,
+        parameters: &[ReplacementParameter],
+        moved_directories: &[DirectoryMove],
+    ) -> BTreeMap<String, String> {
+        let plan_files = parameters
+            .iter()
+            .map(|parameter| parameter.renamed_name())
+            .collect::<HashSet<&str>>();
+        let mut moved_files = BTreeMap::new();
+        for directory_move in moved_directories {
+            for entry in WalkBuilder::new(&directory_move.to)
+                .standard_filters(false)
+                .build()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    entry
+                        .file_type()
+                        .is_some_and(|file_type| file_type.is_file())
+                })
+            {
+                let path = entry.path().to_string_lossy().to_string();
+                if plan_files.contains(path.as_str()) || moved_files.contains_key(&path) {
+                    continue;
+                }
+                if let Ok(rest) = entry.path().strip_prefix(&directory_move.to) {
+                    let previous_path = directory_move.from.join(rest);
+                    moved_files.insert(path, previous_path.to_string_lossy().to_string());
+                }
+            }
+        }
+        moved_files
+    }
+
+    /// Rename the files of the plan, then replace the identify in the other files when requested.
+    fn rename(
+        &self,
+        executor: &dyn RenameExecutor,
+        scaffolds: &[Scaffold],
+        identify: &str,
+        replaced_identify: &str,
+        options: &RenameOptions,
+        update_references: bool,
+    ) -> Result<(RenameResult, Vec<ReplacementParameter>)> {
+        let result = executor.execute(scaffolds, identify, replaced_identify, options)?;
+        if !update_references {
+            return Ok((result, vec![]));
+        }
+        let plan_files = result
+            .parameters
+            .iter()
+            .flat_map(|parameter| [parameter.id(), parameter.renamed_name()])
+            .collect::<HashSet<&str>>();
+        let files = self
+            .project_file_repository()
+            .list()?
+            .into_iter()
+            .filter(|file| !plan_files.contains(file.as_str()))
+            .collect::<Vec<String>>();
+        let references =
+            executor.update_references(&files, identify, replaced_identify, options)?;
+        Ok((result, references))
+    }
+
+    fn print_plan(
+        &self,
+        result: &RenameResult,
+        references: &[ReplacementParameter],
+        output: OutputFormat,
+    ) -> Result<()> {
+        match output {
+            OutputFormat::Json => {
+                let plan = PlannedRename {
+                    directories: &result.moved_directories,
+                    files: result.parameters.iter().map(PlannedFile::new).collect(),
+                    references: references.iter().map(PlannedFile::new).collect(),
+                };
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&plan)
+                        .map_err(|e| MdmgError::Unknown(e.into()))?
+                );
+            }
+            OutputFormat::Text => {
+                for directory_move in result.moved_directories.iter() {
+                    println!(
+                        "{} {} -> {}",
+                        Paint::cyan("MoveDirectory"),
+                        directory_move.from.to_string_lossy(),
+                        directory_move.to.to_string_lossy()
+                    );
+                }
+                for parameter in result.parameters.iter().chain(references.iter()) {
+                    let operation = ReplacementOperation::from(parameter);
+                    if parameter.name_changed() {
+                        println!(
+                            "{} {} -> {}",
+                            Paint::cyan(operation),
+                            parameter.id(),
+                            parameter.renamed_name()
+                        );
+                    } else {
+                        println!("{} {}", Paint::cyan(operation), parameter.id());
+                    }
+                    if parameter.body_changed() {
+                        println!(
+                            "{}",
+                            PatchFormatter::new()
+                                .with_color()
+                                .fmt_patch(&diff(parameter))
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve the files and the created directories of the component from the journal,
+    /// and fall back to rendering the plan.
+    fn resolve_plan(
+        &self,
+        plan_name: &str,
+        identify: &str,
+        variables: Variables,
+        strict: bool,
+    ) -> Result<(RenderedPlan, Vec<String>)> {
+        let journal = self.journal_repository().load()?;
+        match journal.find_component(plan_name, identify) {
+            Some(entry) => {
+                // Reported to stderr so that `--output json` stays parsable.
+                for file in entry.files.iter().filter(|file| file.is_modified()) {
+                    eprintln!(
+                        "{} {}",
+                        Paint::yellow("Modified since generation:"),
+                        file.path
+                    );
+                }
+                let plan = RenderedPlan {
+                    plan_hash: entry.plan_hash.clone(),
+                    front_matter: self.plan_front_matter(plan_name)?,
+                    ctx: MdmgCtx::new(entry.identify.clone())
+                        .with_variables(entry.variables.clone()),
+                    scaffolds: entry
+                        .files
+                        .iter()
+                        .filter(|file| file.hash.is_some())
+                        .map(|file| Scaffold::Complete {
+                            file_name: file.path.clone(),
+                            file_body: read_to_string(&file.path).unwrap_or_default(),
+                        })
+                        .collect(),
+                };
+                Ok((plan, entry.directories.clone()))
+            }
+            None => Ok((
+                render_plan(
+                    self.template_repository().as_ref(),
+                    plan_name,
+                    MdmgCtx::new(identify)
+                        .with_variables(variables)
+                        .with_strict(strict),
+                )?,
+                vec![],
+            )),
+        }
+    }
 }
 
 impl RenameCommand for RenameCommandImpl {
-    fn run(&self, plan_name: &str, identify: &str, replaced_identify: &str) -> Result<()> {
-        let template = self.template_repository().resolve(plan_name.to_string())?;
-        let render_ctx = MdmgCtx::new(identify);
-        let scaffolds = match parse(render(template, &render_ctx)?) {
-            Ok(scaffolds) => scaffolds,
-            Err(_) => return Ok(()),
+    fn run(
+        &self,
+        plan_name: &str,
+        identify: &str,
+        replaced_identify: &str,
+        variables: Variables,
+        flags: RenameFlags,
+    ) -> Result<()> {
+        let (
+            RenderedPlan {
+                plan_hash,
+                front_matter,
+                ctx,
+                scaffolds,
+            },
+            directories,
+        ) = self.resolve_plan(plan_name, identify, variables, flags.strict)?;
+        let options = RenameOptions {
+            word_boundary: front_matter.rename.word_boundary && flags.word_boundary,
+            case_boundary: front_matter.rename.case_boundary || flags.case_boundary,
+            ..front_matter.rename
         };
-        self.rename_executor()
-            .execute(&scaffolds, identify, replaced_identify)
+        if flags.dry_run {
+            let (result, references) = self.rename(
+                self.dry_run_rename_executor().as_ref(),
+                &scaffolds,
+                identify,
+                replaced_identify,
+                &options,
+                flags.update_references,
+            )?;
+            return self.print_plan(&result, &references, flags.output);
+        }
+
+        // The replaced contents and the journal entry are written in the transaction, so nothing is
+        // left behind when a step fails.
+        let transaction = self.transaction();
+        let (parameters, references) = atomically(transaction.as_ref(), || {
+            let previous_hashes = scaffolds
+                .iter()
+                .map(|scaffold| {
+                    self.journal_repository()
+                        .save_file(transaction.as_ref(), Path::new(scaffold.file_name()))
+                })
+                .collect::<Result<Vec<Option<String>>>>()?;
+            let (
+                RenameResult {
+                    parameters,
+                    moved_directories,
+                },
+                references,
+            ) = self.rename(
+                self.rename_executor().as_ref(),
+                &scaffolds,
+                identify,
+                replaced_identify,
+                &options,
+                flags.update_references,
+            )?;
+
+            if !parameters.is_empty() || !references.is_empty() {
+                let renamed_files = parameters
+                    .iter()
+                    .zip(previous_hashes)
+                    .map(|(parameter, previous_hash)| {
+                        Ok(JournalFile {
+                            path: parameter.renamed_name().to_string(),
+                            hash: self.journal_repository().save_file(
+                                transaction.as_ref(),
+                                Path::new(parameter.renamed_name()),
+                            )?,
+                            previous_path: Some(parameter.id().to_string()),
+                            previous_hash,
+                        })
+                    })
+                    .collect::<Result<Vec<JournalFile>>>()?;
+                let moved_files = self.moved_files(&parameters, &moved_directories);
+                let mut updated_references = references
+                    .iter()
+                    .map(|reference| {
+                        Ok(JournalFile {
+                            path: reference.id().to_string(),
+                            hash: self
+                                .journal_repository()
+                                .save_file(transaction.as_ref(), Path::new(reference.id()))?,
+                            previous_path: moved_files.get(reference.id()).cloned(),
+                            previous_hash: Some(self.journal_repository().save_object(
+                                transaction.as_ref(),
+                                reference.before_replace_body().as_bytes(),
+                            )?),
+                        })
+                    })
+                    .collect::<Result<Vec<JournalFile>>>()?;
+                // Files outside the plan moved together with the directories.
+                for (path, previous_path) in moved_files.iter() {
+                    if references.iter().any(|reference| reference.id() == path) {
+                        continue;
+                    }
+                    let hash = self
+                        .journal_repository()
+                        .save_file(transaction.as_ref(), Path::new(path))?;
+                    updated_references.push(JournalFile {
+                        path: path.clone(),
+                        hash: hash.clone(),
+                        previous_path: Some(previous_path.clone()),
+                        previous_hash: hash,
+                    });
+                }
+                let old_directories = parameters
+                    .iter()
+                    .flat_map(|parameter| Path::new(parameter.id()).ancestors().skip(1))
+                    .collect::<HashSet<&Path>>();
+                let created_directories = directories
+                    .iter()
+                    .map(PathBuf::from)
+                    .chain(
+                        parameters
+                            .iter()
+                            .flat_map(|parameter| {
+                                Path::new(parameter.renamed_name()).ancestors().skip(1)
+                            })
+                            .filter(|directory| !old_directories.contains(directory))
+                            .filter(|directory| RemovableDirectories::UnderRoot.contains(directory))
+                            .map(Path::to_path_buf),
+                    )
+                    .filter(|directory| directory.is_dir())
+                    .map(|directory| directory.to_string_lossy().to_string())
+                    .collect::<BTreeSet<String>>()
+                    .into_iter()
+                    .collect::<Vec<String>>();
+                self.journal_repository().append(
+                    JournalEntry::new(
+                        JournalOperation::Rename,
+                        plan_name,
+                        plan_hash,
+                        replaced_identify,
+                        ctx.variables,
+                        renamed_files,
+                    )
+                    .with_previous_identify(ctx.identify)
+                    .with_references(updated_references)
+                    .with_directories(created_directories),
+                )?;
+            }
+            Ok((parameters, references))
+        })?;
+
+        for parameter in parameters.iter().chain(references.iter()) {
+            for occurrence in parameter.occurrences() {
+                self.logger().info(
+                    format!(
+                        "{}:{}: {} -> {}",
+                        parameter.renamed_name(),
+                        occurrence.line,
+                        occurrence.before,
+                        occurrence.after
+                    )
+                    .as_str(),
+                );
+            }
+        }
+        if flags.update_references {
+            self.logger().info(
+                format!(
+                    "Updated references in {} files outside the plan",
+                    references.len()
+                )
+                .as_str(),
+            );
+            for reference in references.iter() {
+                self.logger().info(format!("  {}", reference.id()).as_str());
+            }
+        }
+
+        Ok(())
     }
 }
 
 #[cfg(not(tarpaulin_include))]
 #[cfg(test)]
+#[allow(clippy::arc_with_non_send_sync)]
 mod tests {
-    use crate::commands::rename::{RenameCommand, RenameCommandImpl};
+    use crate::commands::rename::{
+        OutputFormat, PlannedFile, RenameCommand, RenameCommandImpl, RenameFlags,
+    };
     use crate::error::MdmgError;
+    use crate::front_matter::RenameOptions;
+    use crate::generated_file_repository::FSGeneratedFileRepository;
+    use crate::journal::{hash, FSJournalRepository, Journal, JournalEntry, JournalRepository};
     use crate::logger::Logger;
-    use crate::rename_executor::RenameExecutor;
+    use crate::project_file_repository::ProjectFileRepository;
+    use crate::rename_executor::{
+        DefaultRenameExecutor, DryRunReplacementOperationInterpreter, RenameExecutor, RenameResult,
+        ReplacementParameter,
+    };
     use crate::template::Template;
     use crate::template_repository::TemplateRepository;
+    use crate::transaction::{FSTransaction, Transaction};
+    use crate::variable::Variables;
     use derive_more::Constructor;
-
+    use std::env::current_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::path::Path;
+    use std::str::FromStr;
     use std::sync::Arc;
 
+    struct DummyProjectFileRepository;
+
+    impl ProjectFileRepository for DummyProjectFileRepository {
+        fn list(&self) -> crate::Result<Vec<String>> {
+            Ok(vec![])
+        }
+    }
+
+    struct DummyJournalRepository;
+
+    impl JournalRepository for DummyJournalRepository {
+        fn load(&self) -> crate::Result<Journal> {
+            Ok(Journal::default())
+        }
+        fn append(&self, entry: JournalEntry) -> crate::Result<JournalEntry> {
+            Ok(entry)
+        }
+        fn remove(&self, _id: u64) -> crate::Result<()> {
+            Ok(())
+        }
+        fn save_object(
+            &self,
+            _transaction: &dyn Transaction,
+            body: &[u8],
+        ) -> crate::Result<String> {
+            Ok(hash(body))
+        }
+        fn resolve_object(&self, _hash: &str) -> crate::Result<Vec<u8>> {
+            unimplemented!()
+        }
+    }
+
     #[test]
     fn test_rename_command_run_template_is_not_found() {
         #[derive(Constructor, Debug)]
@@ -118,8 +663,18 @@ mod tests {
                 _scaffolds: &[crate::scaffold::Scaffold],
                 _before_identify: &str,
                 _after_identify: &str,
-            ) -> crate::Result<()> {
-                Ok(())
+                _options: &RenameOptions,
+            ) -> crate::Result<RenameResult> {
+                Ok(RenameResult::default())
+            }
+            fn update_references(
+                &self,
+                _files: &[String],
+                _before_identify: &str,
+                _after_identify: &str,
+                _options: &RenameOptions,
+            ) -> crate::Result<Vec<ReplacementParameter>> {
+                Ok(vec![])
             }
         }
 
@@ -127,9 +682,19 @@ mod tests {
             template_repository_instance: Arc::new(DummyTemplateRepository::new()),
             logger_instance: Arc::new(DummyLogger::new()),
             rename_executor_instance: Arc::new(DummyRenameExecutor::new()),
+            dry_run_rename_executor_instance: Arc::new(DummyRenameExecutor::new()),
+            transaction_instance: Arc::new(FSTransaction::new()),
+            journal_repository_instance: Arc::new(DummyJournalRepository),
+            project_file_repository_instance: Arc::new(DummyProjectFileRepository),
         };
 
-        let result = command.run("dummy", "dummy", "dummy");
+        let result = command.run(
+            "dummy",
+            "dummy",
+            "dummy",
+            Variables::new(),
+            RenameFlags::default(),
+        );
         assert!(result.is_err())
     }
 
@@ -164,8 +729,18 @@ mod tests {
                 _scaffolds: &[crate::scaffold::Scaffold],
                 _before_identify: &str,
                 _after_identify: &str,
-            ) -> crate::Result<()> {
-                Ok(())
+                _options: &RenameOptions,
+            ) -> crate::Result<RenameResult> {
+                Ok(RenameResult::default())
+            }
+            fn update_references(
+                &self,
+                _files: &[String],
+                _before_identify: &str,
+                _after_identify: &str,
+                _options: &RenameOptions,
+            ) -> crate::Result<Vec<ReplacementParameter>> {
+                Ok(vec![])
             }
         }
 
@@ -173,15 +748,120 @@ mod tests {
             template_repository_instance: Arc::new(DummyTemplateRepository::new()),
             logger_instance: Arc::new(DummyLogger::new()),
             rename_executor_instance: Arc::new(DummyRenameExecutor::new()),
+            dry_run_rename_executor_instance: Arc::new(DummyRenameExecutor::new()),
+            transaction_instance: Arc::new(FSTransaction::new()),
+            journal_repository_instance: Arc::new(DummyJournalRepository),
+            project_file_repository_instance: Arc::new(DummyProjectFileRepository),
         };
 
-        let result = command.run("dummy", "dummy", "dummy");
+        let result = command.run(
+            "dummy",
+            "dummy",
+            "dummy",
+            Variables::new(),
+            RenameFlags::default(),
+        );
         assert!(result.is_ok())
     }
 
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn test_rename_command_run_dry_run_saves_no_objects() {
+        #[derive(Constructor, Debug)]
+        struct DummyTemplateRepository;
+
+        impl TemplateRepository for DummyTemplateRepository {
+            fn resolve(&self, _template_name: String) -> crate::Result<crate::template::Template> {
+                Ok(Template::new(
+                    "## support/rename_command_run_dry_run_saves_no_objects/{{identify}}.md\n\n```\nfoo\n```",
+                ))
+            }
+            fn list(&self) -> crate::Result<Vec<crate::file::FileName>> {
+                Ok(vec![])
+            }
+        }
+
+        #[derive(Constructor, Debug)]
+        struct DummyLogger;
+
+        impl Logger for DummyLogger {
+            fn info(&self, _log: &str) {}
+            fn debug(&self, _log: &str) {}
+        }
+
+        let base = Path::new("./support/rename_command_run_dry_run_saves_no_objects");
+        assert!(create_dir_all(base).is_ok());
+        assert!(write(base.join("foo.md"), "edited foo").is_ok());
+        let objects = base.join(".objects");
+        let generated_file_repository =
+            Arc::new(FSGeneratedFileRepository::new(current_dir().unwrap()));
+        let dry_run_rename_executor = Arc::new(DefaultRenameExecutor::new(
+            Arc::new(DryRunReplacementOperationInterpreter::new()),
+            generated_file_repository,
+        ));
+        let command = RenameCommandImpl {
+            template_repository_instance: Arc::new(DummyTemplateRepository::new()),
+            logger_instance: Arc::new(DummyLogger::new()),
+            rename_executor_instance: dry_run_rename_executor.clone(),
+            dry_run_rename_executor_instance: dry_run_rename_executor,
+            transaction_instance: Arc::new(FSTransaction::new()),
+            journal_repository_instance: Arc::new(FSJournalRepository::new(
+                base.join(".journal.json"),
+                objects.clone(),
+            )),
+            project_file_repository_instance: Arc::new(DummyProjectFileRepository),
+        };
+
+        let result = command.run(
+            "dummy",
+            "foo",
+            "bar",
+            Variables::new(),
+            RenameFlags {
+                dry_run: true,
+                ..RenameFlags::default()
+            },
+        );
+        assert!(result.is_ok());
+        assert!(!objects.exists());
+        assert!(!base.join(".journal.json").exists());
+        remove_dir_all(base).unwrap();
+    }
+
     #[test]
     #[cfg_attr(not(feature = "fs-test"), ignore)]
     fn test_rename_command_impl_new_test() {
-        RenameCommandImpl::new();
+        RenameCommandImpl::new(current_dir().unwrap(), false);
+    }
+
+    #[test]
+    fn planned_file_returning_operation_and_diff() {
+        let parameter = ReplacementParameter::new(
+            "src/foo.rs".to_string(),
+            "src/bar.rs".to_string(),
+            "struct Foo;\n".to_string(),
+            "struct Bar;\n".to_string(),
+        );
+        let actual = serde_json::to_value(PlannedFile::new(&parameter)).unwrap();
+        assert_eq!(
+            actual,
+            serde_json::json!({
+                "operation": "rename_and_replace",
+                "from": "src/foo.rs",
+                "to": "src/bar.rs",
+                "diff": "--- a/src/foo.rs\n+++ b/src/bar.rs\n@@ -1 +1 @@\n-struct Foo;\n+struct Bar;\n",
+                "occurrences": []
+            })
+        );
+    }
+
+    #[test]
+    fn output_format_from_str() {
+        assert_eq!(OutputFormat::from_str("text").unwrap(), OutputFormat::Text);
+        assert_eq!(OutputFormat::from_str("json").unwrap(), OutputFormat::Json);
+        assert!(matches!(
+            OutputFormat::from_str("yaml"),
+            Err(MdmgError::InvalidOutputFormat(_))
+        ));
     }
 }
@@ -0,0 +1,363 @@
+use crate::error::MdmgError;
+use crate::journal::{FSJournalRepository, JournalEntry, JournalFile, JournalRepository};
+use crate::sandbox::Sandbox;
+use crate::transaction::{atomically, FSTransaction, Transaction};
+use crate::Result;
+
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use yansi::Paint;
+
+pub trait UndoCommand {
+    fn run(&self, id: Option<u64>, list: bool, force: bool) -> Result<()>;
+}
+
+pub struct UndoCommandImpl {
+    journal_repository_instance: Arc<dyn JournalRepository>,
+    transaction_instance: Arc<dyn Transaction>,
+    sandbox: Sandbox,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl UndoCommandImpl {
+    pub fn new(root: PathBuf, allow_outside_root: bool) -> Self {
+        UndoCommandImpl {
+            sandbox: Sandbox::new(root.clone(), allow_outside_root),
+            journal_repository_instance: Arc::new(FSJournalRepository::new(
+                root.join(".mdmg").join(".journal.json"),
+                root.join(".mdmg").join(".objects"),
+            )),
+            transaction_instance: Arc::new(FSTransaction::new()),
+        }
+    }
+}
+
+trait Dependencies {
+    fn journal_repository(&self) -> Arc<dyn JournalRepository>;
+    fn transaction(&self) -> Arc<dyn Transaction>;
+}
+
+impl Dependencies for UndoCommandImpl {
+    fn journal_repository(&self) -> Arc<dyn JournalRepository> {
+        self.journal_repository_instance.clone()
+    }
+    fn transaction(&self) -> Arc<dyn Transaction> {
+        self.transaction_instance.clone()
+    }
+}
+
+impl UndoCommandImpl {
+    fn list(&self, entries: &[JournalEntry]) {
+        for entry in entries.iter().rev() {
+            println!(
+                "{} {} {} {} {}",
+                Paint::cyan(entry.id),
+                entry.recorded_at,
+                entry.operation,
+                entry.plan,
+                entry.identify
+            );
+        }
+    }
+
+    /// Remove the file written by the operation and put back the file it replaced.
+    /// A file replaced in place is overwritten, so it keeps its permissions.
+    fn undo_file(&self, file: &JournalFile) -> Result<()> {
+        let transaction = self.transaction();
+        let path = Path::new(&file.path);
+        let previous_path = Path::new(file.previous_path.as_deref().unwrap_or(&file.path));
+        self.sandbox.check(path)?;
+        self.sandbox.check(previous_path)?;
+        let restore_in_place = file.previous_hash.is_some() && previous_path == path;
+        if file.hash.is_some() && path.exists() && !restore_in_place {
+            transaction.remove_file(path)?;
+            println!("{} {}", Paint::green("Removed:"), file.path);
+        }
+        if let Some(previous_hash) = &file.previous_hash {
+            let body = self.journal_repository().resolve_object(previous_hash)?;
+            if let Some(parent) = previous_path.parent() {
+                transaction.create_dir_all(parent)?;
+            }
+            transaction.write(previous_path, &body)?;
+            println!(
+                "{} {}",
+                Paint::green("Restored:"),
+                previous_path.to_string_lossy()
+            );
+        }
+        Ok(())
+    }
+
+    /// Remove the directories created by the operation, deepest first, when they are empty.
+    fn remove_directories(&self, directories: &[String]) -> Result<()> {
+        let mut directories = directories.iter().map(Path::new).collect::<Vec<&Path>>();
+        directories.sort_by_key(|directory| std::cmp::Reverse(directory.components().count()));
+        for directory in directories {
+            self.sandbox.check(directory)?;
+            if read_dir(directory).is_ok_and(|mut entries| entries.next().is_none()) {
+                self.transaction().remove_dir(directory)?;
+                println!(
+                    "{} {}",
+                    Paint::green("Removed empty directory:"),
+                    directory.to_string_lossy()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl UndoCommand for UndoCommandImpl {
+    fn run(&self, id: Option<u64>, list: bool, force: bool) -> Result<()> {
+        let journal = self.journal_repository().load()?;
+        if list {
+            self.list(&journal.entries);
+            return Ok(());
+        }
+
+        let entry = match id {
+            Some(id) => journal
+                .entries
+                .iter()
+                .find(|entry| entry.id == id)
+                .ok_or(MdmgError::JournalEntryIsNotFound(id))?,
+            None => journal.entries.last().ok_or(MdmgError::NothingToUndo)?,
+        };
+
+        let modified_files = entry
+            .files
+            .iter()
+            .chain(entry.references.iter())
+            .filter(|file| file.is_modified())
+            .map(|file| file.path.clone())
+            .collect::<Vec<String>>();
+        if !modified_files.is_empty() && !force {
+            return Err(MdmgError::ModifiedSinceOperation(modified_files));
+        }
+
+        atomically(self.transaction().as_ref(), || {
+            entry
+                .files
+                .iter()
+                .chain(entry.references.iter())
+                .rev()
+                .try_for_each(|file| self.undo_file(file))?;
+            self.remove_directories(&entry.directories)
+        })?;
+        self.journal_repository().remove(entry.id)?;
+        println!(
+            "{} {} {} {}",
+            Paint::green("Undone:"),
+            entry.operation,
+            entry.plan,
+            entry.identify
+        );
+        Ok(())
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg(test)]
+mod tests {
+    use super::{UndoCommand, UndoCommandImpl};
+    use crate::error::MdmgError;
+    use crate::journal::{
+        hash, FSJournalRepository, JournalEntry, JournalFile, JournalOperation, JournalRepository,
+    };
+    use crate::sandbox::Sandbox;
+    use crate::transaction::FSTransaction;
+    use crate::variable::Variables;
+
+    use std::env::current_dir;
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn setup(base: &Path) -> (UndoCommandImpl, Arc<FSJournalRepository>) {
+        assert!(create_dir_all(base).is_ok());
+        let journal_repository = Arc::new(FSJournalRepository::new(
+            base.join(".journal.json"),
+            base.join(".objects"),
+        ));
+        let command = UndoCommandImpl {
+            journal_repository_instance: journal_repository.clone(),
+            transaction_instance: Arc::new(FSTransaction::new()),
+            sandbox: Sandbox::new(current_dir().unwrap(), false),
+        };
+        (command, journal_repository)
+    }
+
+    fn entry(operation: JournalOperation, files: Vec<JournalFile>) -> JournalEntry {
+        JournalEntry::new(
+            operation,
+            "component",
+            hash(b"plan"),
+            "foo",
+            Variables::new(),
+            files,
+        )
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn undo_command_run_reverts_generate_rename_and_delete() {
+        let base = Path::new("./support/undo_command_run_reverts_operations");
+        let (command, journal_repository) = setup(base);
+        let foo = base.join("foo.md").to_string_lossy().to_string();
+        let bar = base.join("bar.md").to_string_lossy().to_string();
+
+        // generate foo.md, rename it to bar.md and delete bar.md
+        let generated_hash = journal_repository
+            .save_object(&FSTransaction::new(), b"foo")
+            .unwrap();
+        let renamed_hash = journal_repository
+            .save_object(&FSTransaction::new(), b"bar")
+            .unwrap();
+        journal_repository
+            .append(entry(
+                JournalOperation::Generate,
+                vec![JournalFile {
+                    path: foo.clone(),
+                    hash: Some(generated_hash.clone()),
+                    ..JournalFile::default()
+                }],
+            ))
+            .unwrap();
+        journal_repository
+            .append(entry(
+                JournalOperation::Rename,
+                vec![JournalFile {
+                    path: bar.clone(),
+                    hash: Some(renamed_hash.clone()),
+                    previous_path: Some(foo.clone()),
+                    previous_hash: Some(generated_hash),
+                }],
+            ))
+            .unwrap();
+        journal_repository
+            .append(entry(
+                JournalOperation::Delete,
+                vec![JournalFile {
+                    path: bar.clone(),
+                    previous_hash: Some(renamed_hash),
+                    ..JournalFile::default()
+                }],
+            ))
+            .unwrap();
+
+        assert!(command.run(None, false, false).is_ok());
+        assert_eq!(read_to_string(&bar).unwrap(), "bar");
+
+        assert!(command.run(None, false, false).is_ok());
+        assert!(!Path::new(&bar).exists());
+        assert_eq!(read_to_string(&foo).unwrap(), "foo");
+
+        assert!(command.run(Some(1), false, false).is_ok());
+        assert!(!Path::new(&foo).exists());
+        assert!(journal_repository.load().unwrap().entries.is_empty());
+        assert!(matches!(
+            command.run(None, false, false),
+            Err(MdmgError::NothingToUndo)
+        ));
+        remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn undo_command_run_refuses_modified_files() {
+        let base = Path::new("./support/undo_command_run_refuses_modified_files");
+        let (command, journal_repository) = setup(base);
+        let foo = base.join("foo.md").to_string_lossy().to_string();
+
+        journal_repository
+            .append(entry(
+                JournalOperation::Generate,
+                vec![JournalFile {
+                    path: foo.clone(),
+                    hash: Some(hash(b"foo")),
+                    ..JournalFile::default()
+                }],
+            ))
+            .unwrap();
+        assert!(write(&foo, "modified").is_ok());
+
+        assert!(matches!(
+            command.run(None, false, false),
+            Err(MdmgError::ModifiedSinceOperation(_))
+        ));
+        assert!(Path::new(&foo).exists());
+        assert!(matches!(
+            command.run(Some(2), false, false),
+            Err(MdmgError::JournalEntryIsNotFound(2))
+        ));
+
+        assert!(command.run(None, false, true).is_ok());
+        assert!(!Path::new(&foo).exists());
+        remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn undo_command_run_refuses_paths_outside_root() {
+        let base = Path::new("./support/undo_command_run_refuses_paths_outside_root");
+        let (command, journal_repository) = setup(base);
+        let previous_hash = journal_repository
+            .save_object(&FSTransaction::new(), b"outside")
+            .unwrap();
+
+        journal_repository
+            .append(entry(
+                JournalOperation::Delete,
+                vec![JournalFile {
+                    path: "../undo_command_run_refuses_paths_outside_root.md".to_string(),
+                    previous_hash: Some(previous_hash),
+                    ..JournalFile::default()
+                }],
+            ))
+            .unwrap();
+
+        assert!(matches!(
+            command.run(None, false, false),
+            Err(MdmgError::OutsideRoot { .. })
+        ));
+        assert!(!Path::new("../undo_command_run_refuses_paths_outside_root.md").exists());
+        remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn undo_command_run_restores_file_in_place() {
+        use std::fs::{metadata, set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        let base = Path::new("./support/undo_command_run_restores_file_in_place");
+        let (command, journal_repository) = setup(base);
+        let foo = base.join("foo.sh").to_string_lossy().to_string();
+        assert!(write(&foo, "generated").is_ok());
+        assert!(set_permissions(&foo, Permissions::from_mode(0o755)).is_ok());
+
+        journal_repository
+            .append(entry(
+                JournalOperation::Generate,
+                vec![JournalFile {
+                    path: foo.clone(),
+                    hash: Some(hash(b"generated")),
+                    previous_hash: Some(
+                        journal_repository
+                            .save_object(&FSTransaction::new(), b"original")
+                            .unwrap(),
+                    ),
+                    ..JournalFile::default()
+                }],
+            ))
+            .unwrap();
+
+        assert!(command.run(None, false, false).is_ok());
+        assert_eq!(read_to_string(&foo).unwrap(), "original");
+        assert_eq!(metadata(&foo).unwrap().permissions().mode() & 0o777, 0o755);
+        remove_dir_all(base).unwrap();
+    }
+}
@@ -1,38 +1,81 @@
+use crate::sandbox::Sandbox;
 use crate::scaffold::Scaffold;
+use crate::transaction::Transaction;
 use crate::MdmgError;
 use crate::Result;
 
-use std::fs::{read_dir, remove_dir, remove_file};
-use std::path::Path;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::read_dir;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 
 use derive_more::Constructor;
 use yansi::Paint;
 
 pub trait DeleteExecutorDeps {
+    fn exists(&self, path: &Path) -> bool;
     fn delete_file(&self, path: &Path) -> Result<()>;
     fn delete_directory(&self, path: &Path) -> Result<()>;
     fn is_empty_directory(&self, directory_path: &Path) -> bool;
 }
 
+/// Directories that may be removed when they become empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemovableDirectories {
+    /// Any directory inside the project root.
+    UnderRoot,
+    /// Only the directories created by the generation.
+    Only(HashSet<PathBuf>),
+}
+
+impl RemovableDirectories {
+    pub fn contains(&self, path: &Path) -> bool {
+        match self {
+            RemovableDirectories::UnderRoot => {
+                path.components()
+                    .any(|component| matches!(component, Component::Normal(_)))
+                    && path.components().all(|component| {
+                        matches!(component, Component::Normal(_) | Component::CurDir)
+                    })
+            }
+            RemovableDirectories::Only(directories) => directories.contains(path),
+        }
+    }
+}
+
 pub trait DeleteExecutor {
-    fn execute(&self, scaffold: &Scaffold) -> Result<()>;
+    fn execute(
+        &self,
+        scaffold: &Scaffold,
+        removable_directories: &RemovableDirectories,
+    ) -> Result<()>;
 }
 
-#[derive(Clone, Copy, Constructor)]
-pub struct FSDeleteExecutorDeps {}
+#[derive(Clone, Constructor)]
+pub struct FSDeleteExecutorDeps {
+    transaction: Arc<dyn Transaction>,
+    sandbox: Sandbox,
+}
 
 impl DeleteExecutorDeps for FSDeleteExecutorDeps {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
     fn delete_file(&self, path: &Path) -> Result<()> {
-        remove_file(path)
+        self.sandbox.check(path)?;
+        self.transaction
+            .remove_file(path)
             .map_err(|_| MdmgError::FailedDeleteFile(path.to_str().unwrap().to_string()))
     }
     fn delete_directory(&self, path: &Path) -> Result<()> {
-        remove_dir(path)
+        self.sandbox.check(path)?;
+        self.transaction
+            .remove_dir(path)
             .map_err(|_| MdmgError::FailedRemoveParentDirectory(path.to_str().unwrap().to_string()))
     }
     fn is_empty_directory(&self, directory_path: &Path) -> bool {
-        read_dir(directory_path).map_or(false, |read_dir| read_dir.into_iter().count() == 0)
+        read_dir(directory_path).is_ok_and(|read_dir| read_dir.into_iter().count() == 0)
     }
 }
 
@@ -48,7 +91,11 @@ impl<T: DeleteExecutorDeps> FSDeleteExecutor<T> {
 }
 
 impl<T: DeleteExecutorDeps> DeleteExecutor for FSDeleteExecutor<T> {
-    fn execute(&self, scaffold: &Scaffold) -> Result<()> {
+    fn execute(
+        &self,
+        scaffold: &Scaffold,
+        removable_directories: &RemovableDirectories,
+    ) -> Result<()> {
         let file_name = match scaffold {
             Scaffold::Complete {
                 file_name,
@@ -58,19 +105,29 @@ impl<T: DeleteExecutorDeps> DeleteExecutor for FSDeleteExecutor<T> {
         };
         let path = Path::new(file_name);
 
+        if !self.deps.exists(path) {
+            println!(
+                "{} {} (file_not_found)",
+                Paint::yellow("Skip delete:"),
+                file_name
+            );
+            return Ok(());
+        }
+
         self.deps.delete_file(path)?;
         println!("{} {}", Paint::green("Deleted"), file_name);
 
-        let parent_path = &path
-            .parent()
-            .ok_or_else(|| MdmgError::ParentDirectoryIsNotFound(file_name.clone()))?;
-
-        if self.deps.is_empty_directory(parent_path) {
-            self.deps.delete_directory(parent_path)?;
+        for directory in path.ancestors().skip(1) {
+            if !removable_directories.contains(directory)
+                || !self.deps.is_empty_directory(directory)
+            {
+                break;
+            }
+            self.deps.delete_directory(directory)?;
             println!(
                 "{} {}",
                 Paint::green("Deleted empty directory"),
-                parent_path.to_string_lossy()
+                directory.to_string_lossy()
             );
         }
 
@@ -78,17 +135,83 @@ impl<T: DeleteExecutorDeps> DeleteExecutor for FSDeleteExecutor<T> {
     }
 }
 
+/// Print files and directories that would be deleted without touching the file system.
+#[derive(Debug, Default)]
+pub struct DryRunDeleteExecutor {
+    deleted_paths: RefCell<HashSet<PathBuf>>,
+}
+
+impl DryRunDeleteExecutor {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists() && !self.deleted_paths.borrow().contains(path)
+    }
+
+    fn is_empty_directory(&self, directory_path: &Path) -> bool {
+        let deleted_paths = self.deleted_paths.borrow();
+        read_dir(directory_path).is_ok_and(|read_dir| {
+            read_dir
+                .filter_map(|entry| entry.ok())
+                .all(|entry| deleted_paths.contains(&directory_path.join(entry.file_name())))
+        })
+    }
+}
+
+impl DeleteExecutor for DryRunDeleteExecutor {
+    fn execute(
+        &self,
+        scaffold: &Scaffold,
+        removable_directories: &RemovableDirectories,
+    ) -> Result<()> {
+        let file_name = scaffold.file_name();
+        let path = Path::new(file_name);
+
+        if !self.exists(path) {
+            println!(
+                "{} {} (file_not_found)",
+                Paint::yellow("Skip delete:"),
+                file_name
+            );
+            return Ok(());
+        }
+        self.deleted_paths.borrow_mut().insert(path.to_path_buf());
+        println!("{} {}", Paint::green("Would delete"), file_name);
+
+        for directory in path.ancestors().skip(1) {
+            if !removable_directories.contains(directory) || !self.is_empty_directory(directory) {
+                break;
+            }
+            self.deleted_paths
+                .borrow_mut()
+                .insert(directory.to_path_buf());
+            println!(
+                "{} {}",
+                Paint::green("Would delete empty directory"),
+                directory.to_string_lossy()
+            );
+        }
+        Ok(())
+    }
+}
+
 #[cfg(not(tarpaulin_include))]
 #[cfg(test)]
+#[allow(clippy::arc_with_non_send_sync)]
 mod tests {
-    use super::{DeleteExecutor, DeleteExecutorDeps, FSDeleteExecutor, FSDeleteExecutorDeps};
+    use super::{
+        DeleteExecutor, DeleteExecutorDeps, DryRunDeleteExecutor, FSDeleteExecutor,
+        FSDeleteExecutorDeps, RemovableDirectories,
+    };
 
     use crate::error::MdmgError;
+    use crate::sandbox::Sandbox;
     use crate::scaffold::Scaffold;
+    use crate::transaction::FSTransaction;
+    use std::env::current_dir;
 
-    use std::cell::Cell;
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashSet;
     use std::fs::{create_dir, remove_dir, remove_file, write};
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
     use std::sync::Arc;
 
     #[test]
@@ -99,6 +222,9 @@ mod tests {
             pub deleted_directory_path: Cell<Option<String>>,
         }
         impl DeleteExecutorDeps for StubDeleteExecutorDeps {
+            fn exists(&self, _path: &Path) -> bool {
+                true
+            }
             fn delete_file(&self, path: &std::path::Path) -> crate::Result<()> {
                 self.deleted_file_path
                     .replace(path.to_str().map(|s| s.to_string()));
@@ -116,9 +242,12 @@ mod tests {
 
         let stub_deps = Arc::new(StubDeleteExecutorDeps::default());
         let executor = FSDeleteExecutor::new(stub_deps.clone());
-        let actual = executor.execute(&Scaffold::Pending {
-            file_name: "foo/bar.md".to_string(),
-        });
+        let actual = executor.execute(
+            &Scaffold::Pending {
+                file_name: "foo/bar.md".to_string(),
+            },
+            &RemovableDirectories::UnderRoot,
+        );
 
         assert!(actual.is_ok());
         assert_eq!(
@@ -128,6 +257,43 @@ mod tests {
         assert_eq!(stub_deps.deleted_directory_path.take(), None)
     }
 
+    #[test]
+    pub fn skip_delete_when_file_is_not_found() {
+        #[derive(Default)]
+        struct StubDeleteExecutorDeps {
+            pub deleted_file_path: Cell<Option<String>>,
+        }
+        impl DeleteExecutorDeps for StubDeleteExecutorDeps {
+            fn exists(&self, _path: &Path) -> bool {
+                false
+            }
+            fn delete_file(&self, path: &std::path::Path) -> crate::Result<()> {
+                self.deleted_file_path
+                    .replace(path.to_str().map(|s| s.to_string()));
+                Ok(())
+            }
+            fn delete_directory(&self, _path: &Path) -> crate::Result<()> {
+                unreachable!()
+            }
+            fn is_empty_directory(&self, _directory_path: &Path) -> bool {
+                unreachable!()
+            }
+        }
+
+        let stub_deps = Arc::new(StubDeleteExecutorDeps::default());
+        let executor = FSDeleteExecutor::new(stub_deps.clone());
+        let actual = executor.execute(
+            &Scaffold::Complete {
+                file_name: "foo/bar.md".to_string(),
+                file_body: String::default(),
+            },
+            &RemovableDirectories::UnderRoot,
+        );
+
+        assert!(actual.is_ok());
+        assert_eq!(stub_deps.deleted_file_path.take(), None);
+    }
+
     #[test]
     pub fn delete_only_file() {
         #[derive(Default)]
@@ -136,6 +302,9 @@ mod tests {
             pub deleted_directory_path: Cell<Option<String>>,
         }
         impl DeleteExecutorDeps for StubDeleteExecutorDeps {
+            fn exists(&self, _path: &Path) -> bool {
+                true
+            }
             fn delete_file(&self, path: &std::path::Path) -> crate::Result<()> {
                 self.deleted_file_path
                     .replace(path.to_str().map(|s| s.to_string()));
@@ -153,10 +322,13 @@ mod tests {
 
         let stub_deps = Arc::new(StubDeleteExecutorDeps::default());
         let executor = FSDeleteExecutor::new(stub_deps.clone());
-        let actual = executor.execute(&Scaffold::Complete {
-            file_name: "foo/bar.md".to_string(),
-            file_body: String::default(),
-        });
+        let actual = executor.execute(
+            &Scaffold::Complete {
+                file_name: "foo/bar.md".to_string(),
+                file_body: String::default(),
+            },
+            &RemovableDirectories::UnderRoot,
+        );
 
         assert!(actual.is_ok());
         assert_eq!(
@@ -174,6 +346,9 @@ mod tests {
             pub deleted_directory_path: Cell<Option<String>>,
         }
         impl DeleteExecutorDeps for StubDeleteExecutorDeps {
+            fn exists(&self, _path: &Path) -> bool {
+                true
+            }
             fn delete_file(&self, path: &std::path::Path) -> crate::Result<()> {
                 self.deleted_file_path
                     .replace(path.to_str().map(|s| s.to_string()));
@@ -191,10 +366,13 @@ mod tests {
 
         let stub_deps = Arc::new(StubDeleteExecutorDeps::default());
         let executor = FSDeleteExecutor::new(stub_deps.clone());
-        let actual = executor.execute(&Scaffold::Complete {
-            file_name: "foo/bar.md".to_string(),
-            file_body: String::default(),
-        });
+        let actual = executor.execute(
+            &Scaffold::Complete {
+                file_name: "foo/bar.md".to_string(),
+                file_body: String::default(),
+            },
+            &RemovableDirectories::UnderRoot,
+        );
 
         assert!(actual.is_ok());
         assert_eq!(
@@ -207,6 +385,68 @@ mod tests {
         )
     }
 
+    #[test]
+    pub fn delete_file_and_ancestor_directories_become_empty() {
+        #[derive(Default)]
+        struct StubDeleteExecutorDeps {
+            pub deleted_directory_paths: RefCell<Vec<String>>,
+        }
+        impl DeleteExecutorDeps for StubDeleteExecutorDeps {
+            fn exists(&self, _path: &Path) -> bool {
+                true
+            }
+            fn delete_file(&self, _path: &std::path::Path) -> crate::Result<()> {
+                Ok(())
+            }
+            fn delete_directory(&self, path: &Path) -> crate::Result<()> {
+                self.deleted_directory_paths
+                    .borrow_mut()
+                    .push(path.to_string_lossy().to_string());
+                Ok(())
+            }
+            fn is_empty_directory(&self, directory_path: &Path) -> bool {
+                directory_path != Path::new("foo")
+            }
+        }
+        let scaffold = Scaffold::Pending {
+            file_name: "foo/bar/baz/qux.md".to_string(),
+        };
+
+        let stub_deps = Arc::new(StubDeleteExecutorDeps::default());
+        let executor = FSDeleteExecutor::new(stub_deps.clone());
+        assert!(executor
+            .execute(&scaffold, &RemovableDirectories::UnderRoot)
+            .is_ok());
+        assert_eq!(
+            *stub_deps.deleted_directory_paths.borrow(),
+            vec!["foo/bar/baz".to_string(), "foo/bar".to_string()]
+        );
+
+        let stub_deps = Arc::new(StubDeleteExecutorDeps::default());
+        let executor = FSDeleteExecutor::new(stub_deps.clone());
+        let removable_directories =
+            RemovableDirectories::Only(HashSet::from([PathBuf::from("foo/bar/baz")]));
+        assert!(executor.execute(&scaffold, &removable_directories).is_ok());
+        assert_eq!(
+            *stub_deps.deleted_directory_paths.borrow(),
+            vec!["foo/bar/baz".to_string()]
+        );
+    }
+
+    #[test]
+    pub fn removable_directories_contains() {
+        assert!(RemovableDirectories::UnderRoot.contains(Path::new("foo/bar")));
+        assert!(!RemovableDirectories::UnderRoot.contains(Path::new("")));
+        assert!(!RemovableDirectories::UnderRoot.contains(Path::new("/foo")));
+        assert!(!RemovableDirectories::UnderRoot.contains(Path::new("../foo")));
+        assert!(RemovableDirectories::UnderRoot.contains(Path::new("./foo")));
+        assert!(!RemovableDirectories::UnderRoot.contains(Path::new(".")));
+        assert!(
+            !RemovableDirectories::Only(HashSet::new()).contains(Path::new("foo")),
+            "directories existed before generation"
+        );
+    }
+
     #[test]
     pub fn when_failed_delete_file() {
         #[derive(Default)]
@@ -215,6 +455,9 @@ mod tests {
             pub deleted_directory_path: Cell<Option<String>>,
         }
         impl DeleteExecutorDeps for StubDeleteExecutorDeps {
+            fn exists(&self, _path: &Path) -> bool {
+                true
+            }
             fn delete_file(&self, path: &std::path::Path) -> crate::Result<()> {
                 Err(MdmgError::FailedDeleteFile(
                     path.to_string_lossy().to_string(),
@@ -232,10 +475,13 @@ mod tests {
 
         let stub_deps = Arc::new(StubDeleteExecutorDeps::default());
         let executor = FSDeleteExecutor::new(stub_deps.clone());
-        let actual = executor.execute(&Scaffold::Complete {
-            file_name: "foo/bar.md".to_string(),
-            file_body: String::default(),
-        });
+        let actual = executor.execute(
+            &Scaffold::Complete {
+                file_name: "foo/bar.md".to_string(),
+                file_body: String::default(),
+            },
+            &RemovableDirectories::UnderRoot,
+        );
 
         assert!(actual.is_err());
         assert_eq!(stub_deps.deleted_file_path.take(), None);
@@ -250,6 +496,9 @@ mod tests {
             pub deleted_directory_path: Cell<Option<String>>,
         }
         impl DeleteExecutorDeps for StubDeleteExecutorDeps {
+            fn exists(&self, _path: &Path) -> bool {
+                true
+            }
             fn delete_file(&self, path: &std::path::Path) -> crate::Result<()> {
                 self.deleted_file_path
                     .replace(Some(path.to_string_lossy().to_string()));
@@ -267,10 +516,13 @@ mod tests {
 
         let stub_deps = Arc::new(StubDeleteExecutorDeps::default());
         let executor = FSDeleteExecutor::new(stub_deps.clone());
-        let actual = executor.execute(&Scaffold::Complete {
-            file_name: "foo/bar.md".to_string(),
-            file_body: String::default(),
-        });
+        let actual = executor.execute(
+            &Scaffold::Complete {
+                file_name: "foo/bar.md".to_string(),
+                file_body: String::default(),
+            },
+            &RemovableDirectories::UnderRoot,
+        );
 
         assert!(actual.is_err());
         assert_eq!(
@@ -285,10 +537,13 @@ mod tests {
     pub fn fs_delete_executor_deps_delete_file_can_delete_file() {
         let file_path =
             Path::new("./support/fs_delete_executor_deps_delete_file_can_delete_file/dummy.txt");
-        assert!(create_dir(&file_path.parent().unwrap()).is_ok());
+        assert!(create_dir(file_path.parent().unwrap()).is_ok());
         assert!(write(file_path, "dummy").is_ok());
 
-        let deps = FSDeleteExecutorDeps::new();
+        let deps = FSDeleteExecutorDeps::new(
+            Arc::new(FSTransaction::new()),
+            Sandbox::new(current_dir().unwrap(), false),
+        );
         assert!(deps.delete_file(file_path).is_ok());
         assert!(!file_path.exists());
         assert!(remove_dir(file_path.parent().unwrap()).is_ok());
@@ -301,7 +556,10 @@ mod tests {
             "./support/fs_delete_executor_deps_delete_file_failed_when_not_exist_file/dummy.txt",
         );
 
-        let deps = FSDeleteExecutorDeps::new();
+        let deps = FSDeleteExecutorDeps::new(
+            Arc::new(FSTransaction::new()),
+            Sandbox::new(current_dir().unwrap(), false),
+        );
         let actual = deps.delete_file(file_path);
         assert!(actual.is_err());
         assert!(!file_path.exists());
@@ -312,9 +570,12 @@ mod tests {
     pub fn fs_delete_executor_deps_delete_directory_can_delete_directory() {
         let path =
             Path::new("./support/fs_delete_executor_deps_delete_directory_can_delete_directory");
-        assert!(create_dir(&path).is_ok());
+        assert!(create_dir(path).is_ok());
 
-        let deps = FSDeleteExecutorDeps::new();
+        let deps = FSDeleteExecutorDeps::new(
+            Arc::new(FSTransaction::new()),
+            Sandbox::new(current_dir().unwrap(), false),
+        );
         assert!(deps.delete_directory(path).is_ok());
         assert!(!path.exists());
     }
@@ -326,7 +587,10 @@ mod tests {
             "./support/fs_delete_executor_deps_delete_directory_failed_when_not_exist_file/",
         );
 
-        let deps = FSDeleteExecutorDeps::new();
+        let deps = FSDeleteExecutorDeps::new(
+            Arc::new(FSTransaction::new()),
+            Sandbox::new(current_dir().unwrap(), false),
+        );
         let actual = deps.delete_directory(file_path);
         assert!(actual.is_err());
         assert!(!file_path.exists());
@@ -339,7 +603,10 @@ mod tests {
             Path::new("./support/fs_delete_executor_deps_is_empty_directory_return_to_true/");
         assert!(create_dir(path).is_ok());
 
-        let deps = FSDeleteExecutorDeps::new();
+        let deps = FSDeleteExecutorDeps::new(
+            Arc::new(FSTransaction::new()),
+            Sandbox::new(current_dir().unwrap(), false),
+        );
         assert!(deps.is_empty_directory(path));
         assert!(remove_dir(path).is_ok());
     }
@@ -353,9 +620,35 @@ mod tests {
         assert!(create_dir(path).is_ok());
         assert!(write(&file_path, "dummy").is_ok());
 
-        let deps = FSDeleteExecutorDeps::new();
+        let deps = FSDeleteExecutorDeps::new(
+            Arc::new(FSTransaction::new()),
+            Sandbox::new(current_dir().unwrap(), false),
+        );
         assert!(!deps.is_empty_directory(path));
         assert!(remove_file(file_path).is_ok());
         assert!(remove_dir(path).is_ok());
     }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    pub fn dry_run_delete_executor_does_not_delete_files() {
+        let path = Path::new("./support/dry_run_delete_executor_does_not_delete_files/");
+        let file_path = path.join("dummy.txt");
+        assert!(create_dir(path).is_ok());
+        assert!(write(&file_path, "dummy").is_ok());
+
+        let executor = DryRunDeleteExecutor::default();
+        let scaffold = Scaffold::Pending {
+            file_name: file_path.to_string_lossy().to_string(),
+        };
+        assert!(executor
+            .execute(&scaffold, &RemovableDirectories::UnderRoot)
+            .is_ok());
+        assert!(executor.deleted_paths.borrow().contains(&file_path));
+        assert!(executor.deleted_paths.borrow().contains(path));
+        assert!(file_path.exists());
+
+        assert!(remove_file(file_path).is_ok());
+        assert!(remove_dir(path).is_ok());
+    }
 }
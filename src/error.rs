@@ -1,27 +1,109 @@
+use crate::template::TemplateLocation;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum MdmgError {
     #[error("ApplicationError")]
     ApplicationError,
-    #[error("this template is invalid syntax")]
-    TempalteRenderError { reason: String },
+    #[error("failed render the template: {reason}{}", .location.as_ref().map(|location| location.to_string()).unwrap_or_default())]
+    TempalteRenderError {
+        reason: String,
+        location: Option<TemplateLocation>,
+    },
     #[error("pending scaffold exists")]
     ReadPendingScaffoldError { file_name: String },
-    #[error("io error")]
+    #[error("io error: {0}")]
     IoError(#[from] std::io::Error),
     #[error("file name convert error")]
     FileNameConvertError(std::ffi::OsString),
     #[error("template({0}) is not found")]
     TemplateIsNotFound(String),
-    #[error("file path ({0})'s parent path is not found")]
-    ParentDirectoryIsNotFound(String),
     #[error("failed remove parent directory. path: {0}")]
     FailedRemoveParentDirectory(String),
     #[error("failed remove file. path: {0}")]
     FailedDeleteFile(String),
     #[error("file({0}) is not found")]
     GeneratedFileIsNotFound(String),
+    #[error("variable({0}) is invalid. expected key=value")]
+    InvalidVariable(String),
+    #[error("variable({0}) is reserved")]
+    ReservedVariable(String),
+    #[error("failed parse variables file({path}): {reason}")]
+    VariablesFileParseError { path: String, reason: String },
+    #[error("failed parse env file({path}): {reason}")]
+    EnvFileParseError { path: String, reason: String },
+    #[error("failed parse front matter: {0}")]
+    FrontMatterParseError(String),
+    #[error("invalid inputs{}", format_invalid_inputs(.missing, .invalid))]
+    InvalidInputs {
+        missing: Vec<String>,
+        invalid: Vec<String>,
+    },
+    #[error("conflict strategy({0}) is invalid")]
+    InvalidConflictStrategy(String),
+    #[error("files already exist{}", format_file_list(.0))]
+    ConflictingFiles(Vec<String>),
+    #[error("some files are not generated cleanly{}", format_incomplete_generation(.skipped, .conflicted))]
+    IncompleteGeneration {
+        skipped: Vec<String>,
+        conflicted: Vec<String>,
+    },
+    #[error("failed write to {path}: {reason}")]
+    InvalidDestination { path: String, reason: String },
+    #[error("failed parse journal: {0}")]
+    JournalParseError(String),
+    #[error("stored content({0}) is not found")]
+    ObjectIsNotFound(String),
+    #[error("there is no operation to undo")]
+    NothingToUndo,
+    #[error("operation({0}) is not found in the journal")]
+    JournalEntryIsNotFound(u64),
+    #[error("files are modified after the operation. use --force to undo anyway{}", format_file_list(.0))]
+    ModifiedSinceOperation(Vec<String>),
+    #[error("files are modified after generation. use --force to delete anyway{}", format_file_list(.0))]
+    LocallyModifiedFiles(Vec<String>),
+    #[error("each({0}) is not a list")]
+    EachIsNotList(String),
+    #[error("file names must be relative paths inside the project{}", format_file_list(.0))]
+    InvalidFileNames(Vec<String>),
+    #[error("{path} is outside the project root({root}). pass --allow-outside-root to allow it")]
+    OutsideRoot { path: String, root: String },
+    #[error("project root({0}) is not a directory")]
+    RootIsNotDirectory(String),
+    #[error("plans include each other: {}", .0.join(" -> "))]
+    PlanCycle(Vec<String>),
+    #[error("output format({0}) is invalid")]
+    InvalidOutputFormat(String),
+    #[error("rename conflicts with the files{}", format_file_list(.0))]
+    RenameConflicts(Vec<String>),
     #[error("unknown error")]
     Unknown(#[from] anyhow::Error),
 }
+
+fn format_invalid_inputs(missing: &[String], invalid: &[String]) -> String {
+    missing
+        .iter()
+        .map(|name| format!("\n  missing: {}", name))
+        .chain(
+            invalid
+                .iter()
+                .map(|reason| format!("\n  invalid: {}", reason)),
+        )
+        .collect()
+}
+
+fn format_file_list(files: &[String]) -> String {
+    files.iter().map(|file| format!("\n  {}", file)).collect()
+}
+
+fn format_incomplete_generation(skipped: &[String], conflicted: &[String]) -> String {
+    skipped
+        .iter()
+        .map(|file| format!("\n  {} (skipped)", file))
+        .chain(
+            conflicted
+                .iter()
+                .map(|file| format!("\n  {} (conflicted)", file)),
+        )
+        .collect()
+}
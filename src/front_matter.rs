@@ -0,0 +1,381 @@
+use crate::case_style::CaseStyle;
+use crate::error::MdmgError;
+use crate::template::{MdmgCtx, Template};
+use crate::Result;
+
+use derive_more::Display;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Deserialize, Default, PartialEq, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrontMatter {
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub inputs: BTreeMap<String, InputSpec>,
+    pub rename: RenameOptions,
+    /// Plans whose files are generated before the files of this plan.
+    #[serde(deserialize_with = "one_or_many")]
+    pub extends: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn one_or_many<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<String>, D::Error> {
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/// How `rename` finds the identify in the generated files.
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenameOptions {
+    pub cases: Vec<CaseStyle>,
+    pub plural: bool,
+    /// Replace only whole words, so `user` does not match `username`.
+    pub word_boundary: bool,
+    /// Treat a case change as a word boundary too, so `user` matches `User` in `UserService`.
+    pub case_boundary: bool,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        RenameOptions {
+            cases: CaseStyle::all(),
+            plural: true,
+            word_boundary: true,
+            case_boundary: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default, PartialEq, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputSpec {
+    pub required: bool,
+    pub default: Option<Value>,
+    #[serde(rename = "type")]
+    pub input_type: Option<InputType>,
+    pub description: Option<String>,
+    pub pattern: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+pub enum InputType {
+    #[display(fmt = "string")]
+    String,
+    #[display(fmt = "number")]
+    Number,
+    #[display(fmt = "boolean")]
+    Boolean,
+    #[display(fmt = "list")]
+    List,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn from_delimiter(line: &str) -> Option<Format> {
+        match line.trim_end() {
+            "---" => Some(Format::Yaml),
+            "+++" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    fn parse(self, body: &str) -> Result<FrontMatter> {
+        match self {
+            Format::Yaml if body.trim().is_empty() => Ok(FrontMatter::default()),
+            Format::Yaml => serde_yaml::from_str(body)
+                .map_err(|e| MdmgError::FrontMatterParseError(e.to_string())),
+            Format::Toml => {
+                toml::from_str(body).map_err(|e| MdmgError::FrontMatterParseError(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Split the front matter(`---` yaml or `+++` toml block at the top of a plan) from the template.
+pub fn extract(template: Template) -> Result<(FrontMatter, Template)> {
+    let body = template.body();
+    let mut lines = body.split_inclusive('\n');
+    let format = match lines.next().and_then(Format::from_delimiter) {
+        Some(format) => format,
+        None => return Ok((FrontMatter::default(), template)),
+    };
+
+    let mut front_matter_body = String::new();
+    for line in lines.by_ref() {
+        if Format::from_delimiter(line) == Some(format) {
+            let front_matter = format.parse(&front_matter_body)?;
+            let rest = lines.collect::<String>();
+            let line = body[..body.len() - rest.len()].matches('\n').count();
+            return Ok((front_matter, template.part(rest, line)));
+        }
+        front_matter_body.push_str(line);
+    }
+
+    Err(MdmgError::FrontMatterParseError(
+        "closing delimiter is not found".to_string(),
+    ))
+}
+
+impl InputType {
+    fn coerce(self, value: Value) -> std::result::Result<Value, Value> {
+        match (self, value) {
+            (InputType::String, value @ Value::String(_)) => Ok(value),
+            (InputType::String, Value::Number(number)) => Ok(Value::String(number.to_string())),
+            (InputType::String, Value::Bool(boolean)) => Ok(Value::String(boolean.to_string())),
+            (InputType::Number, value @ Value::Number(_)) => Ok(value),
+            (InputType::Boolean, value @ Value::Bool(_)) => Ok(value),
+            (InputType::List, value @ Value::Array(_)) => Ok(value),
+            (_, value) => Err(value),
+        }
+    }
+}
+
+impl InputSpec {
+    fn validate(&self, name: &str, value: Value) -> std::result::Result<Value, String> {
+        let value = match self.input_type {
+            Some(input_type) => input_type
+                .coerce(value)
+                .map_err(|value| format!("{} ({} is not {})", name, value, input_type))?,
+            None => value,
+        };
+
+        if let Some(pattern) = &self.pattern {
+            let regex = Regex::new(pattern)
+                .map_err(|_| format!("{} (pattern {} is invalid)", name, pattern))?;
+            let target = match &value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            if !regex.is_match(&target) {
+                return Err(format!("{} ({} does not match {})", name, target, pattern));
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+impl FrontMatter {
+    /// Fill the default values and validate the supplied values against the declared inputs.
+    pub fn apply(&self, ctx: MdmgCtx) -> Result<MdmgCtx> {
+        let MdmgCtx {
+            mut identify,
+            mut variables,
+            strict,
+        } = ctx;
+        let mut missing = vec![];
+        let mut invalid = vec![];
+
+        for (name, spec) in self.inputs.iter() {
+            let supplied = if name == "identify" {
+                Some(Value::String(identify.clone()))
+            } else {
+                variables.remove(name)
+            };
+            let value = match supplied.or_else(|| spec.default.clone()) {
+                Some(value) => value,
+                None if spec.required => {
+                    missing.push(name.clone());
+                    continue;
+                }
+                None => continue,
+            };
+            match spec.validate(name, value) {
+                Ok(Value::String(value)) if name == "identify" => identify = value,
+                Ok(value) => {
+                    variables.insert(name.clone(), value);
+                }
+                Err(reason) => invalid.push(reason),
+            }
+        }
+
+        if missing.is_empty() && invalid.is_empty() {
+            Ok(MdmgCtx::new(identify)
+                .with_variables(variables)
+                .with_strict(strict))
+        } else {
+            Err(MdmgError::InvalidInputs { missing, invalid })
+        }
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg(test)]
+mod tests {
+    use super::{extract, FrontMatter, InputSpec, InputType, RenameOptions};
+    use crate::case_style::CaseStyle;
+    use crate::error::MdmgError;
+    use crate::template::{MdmgCtx, Template};
+    use crate::variable::Variables;
+
+    use indoc::indoc;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn extract_returning_default_when_front_matter_is_not_exist() {
+        let (front_matter, template) = extract(Template::new("## foo.md")).unwrap();
+        assert_eq!(front_matter, FrontMatter::default());
+        assert_eq!(template, Template::new("## foo.md"));
+    }
+
+    #[test]
+    fn extract_returning_yaml_front_matter() {
+        let (front_matter, template) = extract(Template::new(indoc! {"
+            ---
+            description: React component
+            tags: [react]
+            inputs:
+              module:
+                required: true
+                type: string
+                pattern: ^[a-z]+$
+            ---
+            ## foo.md
+        "}))
+        .unwrap();
+
+        assert_eq!(
+            front_matter.description,
+            Some("React component".to_string())
+        );
+        assert_eq!(front_matter.tags, vec!["react".to_string()]);
+        assert_eq!(
+            front_matter.inputs.get("module"),
+            Some(&InputSpec {
+                required: true,
+                input_type: Some(InputType::String),
+                pattern: Some("^[a-z]+$".to_string()),
+                ..InputSpec::default()
+            })
+        );
+        assert_eq!(template, Template::new("## foo.md\n").with_origin("", 9));
+    }
+
+    #[test]
+    fn extract_returning_rename_options() {
+        let (front_matter, _) = extract(Template::new(indoc! {"
+            ---
+            rename:
+              cases: [pascal, screaming_snake]
+              plural: false
+            ---
+            ## foo.md
+        "}))
+        .unwrap();
+
+        assert_eq!(
+            front_matter.rename,
+            RenameOptions {
+                cases: vec![CaseStyle::Pascal, CaseStyle::ScreamingSnake],
+                plural: false,
+                word_boundary: true,
+                case_boundary: false,
+            }
+        );
+    }
+
+    #[test]
+    fn extract_returning_toml_front_matter() {
+        let (front_matter, template) = extract(Template::new(indoc! {r#"
+            +++
+            description = "React component"
+
+            [inputs.with_tests]
+            default = false
+            +++
+            ## foo.md
+        "#}))
+        .unwrap();
+
+        assert_eq!(
+            front_matter.description,
+            Some("React component".to_string())
+        );
+        assert_eq!(
+            front_matter.inputs.get("with_tests"),
+            Some(&InputSpec {
+                default: Some(json!(false)),
+                ..InputSpec::default()
+            })
+        );
+        assert_eq!(template, Template::new("## foo.md\n").with_origin("", 6));
+    }
+
+    #[test]
+    fn extract_is_error_when_front_matter_is_not_closed() {
+        assert!(extract(Template::new("---\ndescription: foo\n## foo.md")).is_err());
+        assert!(extract(Template::new("---\nunknown: foo\n---\n## foo.md")).is_err());
+    }
+
+    fn front_matter() -> FrontMatter {
+        let (front_matter, _) = extract(Template::new(indoc! {"
+            ---
+            inputs:
+              identify:
+                pattern: ^[A-Z]
+              module:
+                required: true
+              with_tests:
+                type: boolean
+                default: true
+              version:
+                type: string
+            ---
+        "}))
+        .unwrap();
+        front_matter
+    }
+
+    #[test]
+    fn apply_returning_ctx_with_default_values() {
+        let mut variables = Variables::new();
+        variables.insert("module".to_string(), json!("account"));
+        variables.insert("version".to_string(), json!(1));
+
+        let ctx = front_matter()
+            .apply(MdmgCtx::new("Foo").with_variables(variables))
+            .unwrap();
+
+        assert_eq!(ctx.identify, "Foo".to_string());
+        assert_eq!(ctx.variables.get("module"), Some(&json!("account")));
+        assert_eq!(ctx.variables.get("with_tests"), Some(&json!(true)));
+        assert_eq!(ctx.variables.get("version"), Some(&json!("1")));
+    }
+
+    #[test]
+    fn apply_is_error_when_inputs_are_missing_or_invalid() {
+        let mut variables = Variables::new();
+        variables.insert("with_tests".to_string(), json!("yes"));
+
+        let actual = front_matter().apply(MdmgCtx::new("foo").with_variables(variables));
+
+        match actual {
+            Err(MdmgError::InvalidInputs { missing, invalid }) => {
+                assert_eq!(missing, vec!["module".to_string()]);
+                assert_eq!(invalid.len(), 2);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use crate::error::MdmgError;
+use crate::transaction::Transaction;
+use crate::variable::Variables;
+use crate::Result;
+
+use derive_more::{Constructor, Display};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{read, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalOperation {
+    #[display(fmt = "generate")]
+    Generate,
+    #[display(fmt = "delete")]
+    Delete,
+    #[display(fmt = "rename")]
+    Rename,
+}
+
+/// A file touched by an operation. `hash` is the content after the operation and
+/// `previous_hash` is the content before it. `None` means the file does not exist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct JournalFile {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_hash: Option<String>,
+}
+
+impl JournalFile {
+    /// Returns true when the file on disk is not the one recorded by the operation.
+    pub fn is_modified(&self) -> bool {
+        let current_hash = read(&self.path).ok().map(|body| hash(&body));
+        current_hash != self.hash
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    #[serde(default)]
+    pub id: u64,
+    pub operation: JournalOperation,
+    pub plan: String,
+    pub plan_hash: String,
+    pub identify: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_identify: Option<String>,
+    #[serde(default)]
+    pub variables: Variables,
+    pub files: Vec<JournalFile>,
+    /// Files outside the plan updated by the operation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<JournalFile>,
+    /// Directories created by the operation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub directories: Vec<String>,
+    #[serde(default)]
+    pub recorded_at: String,
+}
+
+impl JournalEntry {
+    pub fn new<T: Into<String>, U: Into<String>>(
+        operation: JournalOperation,
+        plan: T,
+        plan_hash: String,
+        identify: U,
+        variables: Variables,
+        files: Vec<JournalFile>,
+    ) -> Self {
+        JournalEntry {
+            id: 0,
+            operation,
+            plan: plan.into(),
+            plan_hash,
+            identify: identify.into(),
+            previous_identify: None,
+            variables,
+            files,
+            references: vec![],
+            directories: vec![],
+            recorded_at: String::new(),
+        }
+    }
+
+    pub fn with_references(self, references: Vec<JournalFile>) -> Self {
+        JournalEntry { references, ..self }
+    }
+
+    pub fn with_directories(self, directories: Vec<String>) -> Self {
+        JournalEntry {
+            directories,
+            ..self
+        }
+    }
+
+    pub fn with_previous_identify<T: Into<String>>(self, previous_identify: T) -> Self {
+        JournalEntry {
+            previous_identify: Some(previous_identify.into()),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Journal {
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Find the latest operation that produced the component and is not deleted or renamed after that.
+    pub fn find_component(&self, plan: &str, identify: &str) -> Option<&JournalEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.plan == plan)
+            .find(|entry| {
+                entry.identify == identify || entry.previous_identify.as_deref() == Some(identify)
+            })
+            .filter(|entry| {
+                entry.identify == identify && entry.operation != JournalOperation::Delete
+            })
+    }
+
+    fn next_id(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.id).max().unwrap_or(0) + 1
+    }
+}
+
+pub fn hash(body: &[u8]) -> String {
+    Sha256::digest(body)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Keeps the operations and the contents of the files they replaced, so that they can be undone.
+pub trait JournalRepository {
+    fn load(&self) -> Result<Journal>;
+    fn append(&self, entry: JournalEntry) -> Result<JournalEntry>;
+    fn remove(&self, id: u64) -> Result<()>;
+    /// Store the content through the transaction, so no object is left behind when the operation is rolled back.
+    fn save_object(&self, transaction: &dyn Transaction, body: &[u8]) -> Result<String>;
+    fn resolve_object(&self, hash: &str) -> Result<Vec<u8>>;
+
+    /// Keep the current content of the file. Returns `None` when the file does not exist.
+    fn save_file(&self, transaction: &dyn Transaction, path: &Path) -> Result<Option<String>> {
+        match read(path) {
+            Ok(body) => self.save_object(transaction, &body).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Constructor)]
+pub struct FSJournalRepository {
+    path: PathBuf,
+    objects: PathBuf,
+}
+
+impl FSJournalRepository {
+    fn save(&self, journal: &Journal) -> Result<()> {
+        let body = serde_json::to_string_pretty(journal)
+            .map_err(|e| MdmgError::JournalParseError(e.to_string()))?;
+        write(&self.path, body)?;
+        Ok(())
+    }
+}
+
+impl JournalRepository for FSJournalRepository {
+    fn load(&self) -> Result<Journal> {
+        if !Path::new(&self.path).exists() {
+            return Ok(Journal::default());
+        }
+        let body = read_to_string(&self.path)?;
+        serde_json::from_str(&body).map_err(|e| MdmgError::JournalParseError(e.to_string()))
+    }
+
+    fn append(&self, entry: JournalEntry) -> Result<JournalEntry> {
+        let mut journal = self.load()?;
+        let entry = JournalEntry {
+            id: journal.next_id(),
+            recorded_at: chrono::Local::now().to_rfc3339(),
+            ..entry
+        };
+        journal.entries.push(entry.clone());
+        self.save(&journal)?;
+        Ok(entry)
+    }
+
+    fn remove(&self, id: u64) -> Result<()> {
+        let mut journal = self.load()?;
+        journal.entries.retain(|entry| entry.id != id);
+        self.save(&journal)
+    }
+
+    fn save_object(&self, transaction: &dyn Transaction, body: &[u8]) -> Result<String> {
+        let hash = hash(body);
+        let path = self.objects.join(&hash);
+        if !path.exists() {
+            transaction.create_dir_all(&self.objects)?;
+            transaction.write(&path, body)?;
+        }
+        Ok(hash)
+    }
+
+    fn resolve_object(&self, hash: &str) -> Result<Vec<u8>> {
+        read(self.objects.join(hash)).map_err(|_| MdmgError::ObjectIsNotFound(hash.to_string()))
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg(test)]
+mod tests {
+    use super::{
+        hash, FSJournalRepository, Journal, JournalEntry, JournalFile, JournalOperation,
+        JournalRepository,
+    };
+    use crate::transaction::{FSTransaction, Transaction};
+    use crate::variable::Variables;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use std::path::Path;
+
+    fn entry(operation: JournalOperation, identify: &str) -> JournalEntry {
+        JournalEntry::new(
+            operation,
+            "component",
+            hash(b"plan"),
+            identify,
+            Variables::new(),
+            vec![JournalFile {
+                path: format!("{}.md", identify),
+                hash: Some(hash(b"body")),
+                ..JournalFile::default()
+            }],
+        )
+    }
+
+    #[test]
+    fn hash_returning_sha256_hex() {
+        assert_eq!(
+            hash(b"hello"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn journal_find_component() {
+        let journal = Journal {
+            entries: vec![
+                entry(JournalOperation::Generate, "foo"),
+                entry(JournalOperation::Generate, "bar"),
+                entry(JournalOperation::Delete, "bar"),
+                entry(JournalOperation::Generate, "baz"),
+                entry(JournalOperation::Rename, "qux").with_previous_identify("baz"),
+            ],
+        };
+
+        assert_eq!(
+            journal.find_component("component", "foo"),
+            Some(&journal.entries[0])
+        );
+        assert_eq!(journal.find_component("component", "bar"), None);
+        assert_eq!(journal.find_component("component", "baz"), None);
+        assert_eq!(
+            journal.find_component("component", "qux"),
+            Some(&journal.entries[4])
+        );
+        assert_eq!(journal.find_component("other", "foo"), None);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn fs_journal_repository_append_and_load() {
+        let base = Path::new("./support/fs_journal_repository_append_and_load");
+        assert!(create_dir_all(base).is_ok());
+        let repository =
+            FSJournalRepository::new(base.join(".journal.json"), base.join(".objects"));
+
+        assert_eq!(repository.load().unwrap(), Journal::default());
+        let first = repository
+            .append(entry(JournalOperation::Generate, "foo"))
+            .unwrap();
+        let second = repository
+            .append(entry(JournalOperation::Delete, "foo"))
+            .unwrap();
+
+        assert_eq!(first.id, 1);
+        assert_eq!(second.id, 2);
+        assert_eq!(
+            repository.load().unwrap().entries,
+            vec![first, second.clone()]
+        );
+        assert!(repository.remove(1).is_ok());
+        assert_eq!(repository.load().unwrap().entries, vec![second]);
+        remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn fs_journal_repository_save_and_resolve_object() {
+        let base = Path::new("./support/fs_journal_repository_save_and_resolve_object");
+        let repository =
+            FSJournalRepository::new(base.join(".journal.json"), base.join(".objects"));
+
+        let transaction = FSTransaction::new();
+        let hash = repository.save_object(&transaction, b"hello").unwrap();
+        assert_eq!(hash, super::hash(b"hello"));
+        assert_eq!(repository.resolve_object(&hash).unwrap(), b"hello".to_vec());
+        assert!(repository.resolve_object("unknown").is_err());
+
+        // Rolling back the operation leaves no object behind.
+        assert!(transaction.rollback().is_ok());
+        assert!(repository.resolve_object(&hash).is_err());
+        assert!(!base.exists());
+    }
+}
@@ -1,26 +1,37 @@
+mod case_style;
 mod commands;
 mod delete_executor;
 mod error;
 mod file;
+mod front_matter;
 mod generated_file_repository;
+mod journal;
 mod logger;
 mod markdown;
 mod opts;
-mod output;
+mod plan;
+mod project_file_repository;
+mod project_root;
 mod rename_executor;
+mod sandbox;
 mod scaffold;
 mod scaffold_executor;
+mod snapshot_repository;
 mod template;
 mod template_repository;
+mod transaction;
+mod variable;
 
-use commands::rename::{RenameCommand, RenameCommandImpl};
+use commands::rename::{OutputFormat, RenameCommand, RenameCommandImpl, RenameFlags};
 
 use crate::commands::delete::{DeleteCommand, DeleteCommandImpl};
 use crate::commands::generate::{GenerateCommand, GenerateCommandImpl};
 use crate::commands::list::{ListCommand, ListCommandImpl};
 use crate::commands::setup::{SetupCommand, SetupCommandImpl};
+use crate::commands::undo::{UndoCommand, UndoCommandImpl};
 use crate::error::MdmgError;
 use crate::opts::{parse_cli_args, Mdmg};
+use crate::project_root::enter_root;
 
 pub type Result<T> = anyhow::Result<T, MdmgError>;
 
@@ -30,12 +41,26 @@ pub fn run() -> Result<()> {
             template_name,
             identify,
             dry_run,
+            on_conflict,
+            allow_outside_root,
+            no_strict,
+            root_opts,
+            variable_opts,
         } => {
-            let command = GenerateCommandImpl::new();
-            command.run(template_name, identify, dry_run)?;
+            // The variable files are relative to the working directory, so load them before entering the root.
+            let variables = variable_opts.into_variables()?;
+            let command = GenerateCommandImpl::new(enter_root(root_opts.root)?, allow_outside_root);
+            command.run(
+                template_name,
+                identify,
+                variables,
+                dry_run,
+                on_conflict,
+                !no_strict,
+            )?;
         }
-        Mdmg::List {} => {
-            let command = ListCommandImpl::default();
+        Mdmg::List { root_opts } => {
+            let command = ListCommandImpl::new(enter_root(root_opts.root)?);
             command.run()?;
         }
         Mdmg::Setup {} => {
@@ -45,17 +70,64 @@ pub fn run() -> Result<()> {
         Mdmg::Delete {
             template_name,
             identify,
+            dry_run,
+            force,
+            allow_outside_root,
+            no_strict,
+            root_opts,
+            variable_opts,
         } => {
-            let command = DeleteCommandImpl::new();
-            command.run(template_name, identify)?;
+            let variables = variable_opts.into_variables()?;
+            let command = DeleteCommandImpl::new(enter_root(root_opts.root)?, allow_outside_root);
+            command.run(
+                template_name,
+                identify,
+                variables,
+                dry_run,
+                force,
+                !no_strict,
+            )?;
         }
         Mdmg::Rename {
             template_name,
             identify,
             replaced_identify,
+            no_word_boundary,
+            case_boundary,
+            update_references,
+            dry_run,
+            output,
+            allow_outside_root,
+            no_strict,
+            root_opts,
+            variable_opts,
+        } => {
+            let variables = variable_opts.into_variables()?;
+            let command = RenameCommandImpl::new(enter_root(root_opts.root)?, allow_outside_root);
+            command.run(
+                &template_name,
+                &identify,
+                &replaced_identify,
+                variables,
+                RenameFlags {
+                    word_boundary: !no_word_boundary,
+                    case_boundary,
+                    update_references,
+                    dry_run,
+                    output: output.unwrap_or(OutputFormat::Text),
+                    strict: !no_strict,
+                },
+            )?;
+        }
+        Mdmg::Undo {
+            id,
+            list,
+            force,
+            allow_outside_root,
+            root_opts,
         } => {
-            let command = RenameCommandImpl::new();
-            command.run(&template_name, &identify, &replaced_identify)?;
+            let command = UndoCommandImpl::new(enter_root(root_opts.root)?, allow_outside_root);
+            command.run(id, list, force)?;
         }
     };
     Ok(())
@@ -3,6 +3,7 @@ use derive_more::Constructor;
 #[cfg(not(tarpaulin_include))]
 pub trait Logger {
     fn info(&self, info: &str);
+    #[allow(dead_code)]
     fn debug(&self, log: &str);
 }
 
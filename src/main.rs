@@ -1,6 +1,10 @@
 use mdmg::run;
-use mdmg::Result;
+use std::process::exit;
+use yansi::Paint;
 
-fn main() -> Result<()> {
-    run()
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{} {}", Paint::red("error:"), e);
+        exit(1);
+    }
 }
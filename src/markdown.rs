@@ -1,77 +1,78 @@
 use crate::scaffold::Scaffold;
 use crate::Result;
-use comrak::nodes::{AstNode, NodeValue};
+use comrak::nodes::NodeValue;
 use comrak::{parse_document, Arena, ComrakOptions};
 
+/// Returns false when the info string of the code block has a `when:` attribute with a falsy value.
+fn is_enabled(info: &str) -> bool {
+    let mut words = info.split_whitespace();
+    while let Some(word) = words.next() {
+        if let Some(value) = word.strip_prefix("when:") {
+            let value = if value.is_empty() {
+                words.next().unwrap_or("")
+            } else {
+                value
+            };
+            return !matches!(value, "" | "false" | "0" | "no" | "null");
+        }
+    }
+    true
+}
+
 pub fn parse<T: Into<String>>(markdown: T) -> Result<Vec<Scaffold>> {
     let arena = Arena::new();
     let doc = parse_document(&arena, markdown.into().as_ref(), &ComrakOptions::default());
 
     let mut scaffolds: Vec<Scaffold> = vec![];
+    // True while the section under a heading rendered empty is read, so that its code block is dropped.
+    let mut skipping = false;
 
-    fn iter_nodes<'a, F>(
-        node: &'a AstNode<'a>,
-        scaffolds: &'a mut Vec<Scaffold>,
-        f: &F,
-    ) -> &'a mut Vec<Scaffold>
-    where
-        F: Fn(&'a AstNode<'a>, &'a mut Vec<Scaffold>) -> &'a mut Vec<Scaffold>,
-    {
-        let mut scaffolds = f(node, scaffolds);
-        for c in node.children() {
-            scaffolds = iter_nodes(c, scaffolds, f);
-        }
-        scaffolds
-    }
-
-    let scaffolds = iter_nodes(doc, &mut scaffolds, &|node, scaffolds| {
-        if let NodeValue::Text(txt_vec) = node.data.clone().into_inner().value {
-            if let Some(parent_node) = node.parent() {
-                if let NodeValue::Heading(heading) = parent_node.data.clone().into_inner().value {
-                    if heading.level != 2 {
-                        return scaffolds;
-                    }
-                    if txt_vec.is_empty() {
-                        return scaffolds;
-                    }
-                    let title_txt = String::from_utf8(txt_vec);
-                    if let Ok(file_name) = title_txt {
-                        scaffolds.push(Scaffold::Pending { file_name })
-                    }
+    for node in doc.descendants() {
+        match node.data.clone().into_inner().value {
+            NodeValue::Heading(heading) if heading.level == 2 => {
+                let file_name = node
+                    .children()
+                    .filter_map(|child| match child.data.clone().into_inner().value {
+                        NodeValue::Text(txt_vec) => String::from_utf8(txt_vec).ok(),
+                        _ => None,
+                    })
+                    .collect::<String>();
+                skipping = file_name.trim().is_empty();
+                if !skipping {
+                    scaffolds.push(Scaffold::Pending { file_name })
                 }
             }
-        }
-
-        if let NodeValue::CodeBlock(ncb) = node.data.clone().into_inner().value {
-            if ncb.literal.is_empty() {
-                return scaffolds;
-            }
-
-            let code_block_txt = String::from_utf8(ncb.literal);
-            let code_block = code_block_txt.expect("code block text is not found");
-            let pending_scaffold_position_opt = scaffolds.iter().position(|scaffold| -> bool {
-                if let Scaffold::Pending {
-                    file_name: _file_name,
-                } = scaffold
-                {
-                    return true;
+            NodeValue::CodeBlock(ncb) => {
+                if ncb.literal.is_empty() {
+                    continue;
                 }
-                false
-            });
-            if let Some(pending_scaffold_position) = pending_scaffold_position_opt {
-                let pending_scaffold = scaffolds.swap_remove(pending_scaffold_position);
-                if let Scaffold::Pending { file_name } = pending_scaffold {
-                    scaffolds.push(Scaffold::Complete {
-                        file_name,
-                        file_body: code_block,
-                    })
+                if skipping {
+                    skipping = false;
+                    continue;
+                }
+
+                let code_block_txt = String::from_utf8(ncb.literal);
+                let code_block = code_block_txt.expect("code block text is not found");
+                let pending_scaffold_position_opt = scaffolds
+                    .iter()
+                    .position(|scaffold| matches!(scaffold, Scaffold::Pending { .. }));
+                if let Some(pending_scaffold_position) = pending_scaffold_position_opt {
+                    let pending_scaffold = scaffolds.swap_remove(pending_scaffold_position);
+                    if let Scaffold::Pending { file_name } = pending_scaffold {
+                        if is_enabled(&String::from_utf8_lossy(&ncb.info)) {
+                            scaffolds.push(Scaffold::Complete {
+                                file_name,
+                                file_body: code_block,
+                            })
+                        }
+                    }
                 }
             }
+            _ => {}
         }
-        scaffolds
-    });
+    }
 
-    Ok(scaffolds.clone())
+    Ok(scaffolds)
 }
 
 #[cfg(test)]
@@ -125,4 +126,46 @@ mod tests {
             }]
         )
     }
+
+    #[test]
+    fn parse_skips_sections_with_empty_heading_or_false_condition() {
+        let markdown = r#"
+## src/foo.rs
+
+```rust
+foo
+```
+
+## 
+
+```rust
+skipped by the heading
+```
+
+## src/bar.rs
+
+```rust when:false
+skipped by the condition
+```
+
+## src/baz.rs
+
+```rust when: true
+baz
+```
+"#;
+        assert_eq!(
+            parse(markdown).unwrap(),
+            vec![
+                Scaffold::Complete {
+                    file_name: "src/foo.rs".to_string(),
+                    file_body: "foo\n".to_string()
+                },
+                Scaffold::Complete {
+                    file_name: "src/baz.rs".to_string(),
+                    file_body: "baz\n".to_string()
+                }
+            ]
+        )
+    }
 }
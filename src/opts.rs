@@ -1,5 +1,70 @@
+use crate::commands::rename::OutputFormat;
+use crate::scaffold_executor::ConflictStrategy;
+use crate::variable::{
+    load_env_file, load_variables_file, merge_variables, parse_variable, Variables,
+};
+use crate::Result;
+
+use serde_json::Value;
+use std::env::{set_var, var_os};
+use std::path::PathBuf;
 use structopt::{clap, StructOpt};
 
+#[derive(StructOpt)]
+pub struct VariableOpts {
+    #[structopt(
+        long = "var",
+        number_of_values = 1,
+        parse(try_from_str = parse_variable),
+        help = "Template variable (key=value). Can be specified multiple times"
+    )]
+    pub vars: Vec<(String, Value)>,
+
+    #[structopt(
+        long = "vars-file",
+        parse(from_os_str),
+        help = "Template variables file(json, yaml or toml)"
+    )]
+    pub vars_file: Option<PathBuf>,
+
+    #[structopt(
+        long = "env-file",
+        parse(from_os_str),
+        help = "Environment variables file(KEY=VALUE) used by the env helper"
+    )]
+    pub env_file: Option<PathBuf>,
+}
+
+impl VariableOpts {
+    /// Collect the template variables. The env file is exported to the environment without overriding
+    /// the variables already set.
+    pub fn into_variables(self) -> Result<Variables> {
+        if let Some(path) = &self.env_file {
+            for (key, value) in load_env_file(path)? {
+                if var_os(&key).is_none() {
+                    set_var(key, value);
+                }
+            }
+        }
+        let base = match &self.vars_file {
+            Some(path) => load_variables_file(path)?,
+            None => Variables::new(),
+        };
+        Ok(merge_variables(base, self.vars))
+    }
+}
+
+#[derive(StructOpt)]
+pub struct RootOpts {
+    #[structopt(
+        long = "root",
+        env = "MDMG_ROOT",
+        parse(from_os_str),
+        help = "Project root. Defaults to the nearest directory that has .mdmg"
+    )]
+    pub root: Option<PathBuf>,
+}
+
 #[derive(StructOpt)]
 #[structopt(name = clap::crate_name!(), about = clap::crate_description!(), author = clap::crate_authors!(), version = clap::crate_version!(), setting(clap::AppSettings::ColoredHelp))]
 pub enum Mdmg {
@@ -13,9 +78,38 @@ pub enum Mdmg {
 
         #[structopt(short = "d", long = "dry-run")]
         dry_run: bool,
+
+        #[structopt(
+            long = "on-conflict",
+            default_value = "skip",
+            possible_values = &ConflictStrategy::variants(),
+            help = "How to handle files that already exist"
+        )]
+        on_conflict: ConflictStrategy,
+
+        #[structopt(
+            long = "allow-outside-root",
+            help = "Allow writing and deleting files outside the project root"
+        )]
+        allow_outside_root: bool,
+
+        #[structopt(
+            long = "no-strict",
+            help = "Render undefined variables as empty strings and allow any file name"
+        )]
+        no_strict: bool,
+
+        #[structopt(flatten)]
+        root_opts: RootOpts,
+
+        #[structopt(flatten)]
+        variable_opts: VariableOpts,
     },
     #[structopt(about = "Show available template lists")]
-    List {},
+    List {
+        #[structopt(flatten)]
+        root_opts: RootOpts,
+    },
     #[structopt(about = "Setup mdmg command environment(Create a .mdmg directory)")]
     Setup {},
     #[structopt(about = "Delete files Written in template")]
@@ -25,6 +119,33 @@ pub enum Mdmg {
 
         #[structopt()]
         identify: String,
+
+        #[structopt(short = "d", long = "dry-run")]
+        dry_run: bool,
+
+        #[structopt(
+            long = "force",
+            help = "Delete files even if they are modified after generation"
+        )]
+        force: bool,
+
+        #[structopt(
+            long = "allow-outside-root",
+            help = "Allow writing and deleting files outside the project root"
+        )]
+        allow_outside_root: bool,
+
+        #[structopt(
+            long = "no-strict",
+            help = "Render undefined variables as empty strings and allow any file name"
+        )]
+        no_strict: bool,
+
+        #[structopt(flatten)]
+        root_opts: RootOpts,
+
+        #[structopt(flatten)]
+        variable_opts: VariableOpts,
     },
     Rename {
         #[structopt()]
@@ -35,6 +156,76 @@ pub enum Mdmg {
 
         #[structopt()]
         replaced_identify: String,
+
+        #[structopt(
+            long = "no-word-boundary",
+            help = "Replace the identify even inside other words"
+        )]
+        no_word_boundary: bool,
+
+        #[structopt(
+            long = "case-boundary",
+            help = "Also treat a case change as a word boundary, so UserService matches User"
+        )]
+        case_boundary: bool,
+
+        #[structopt(
+            long = "update-references",
+            help = "Also replace the identify in files outside the plan"
+        )]
+        update_references: bool,
+
+        #[structopt(short = "d", long = "dry-run")]
+        dry_run: bool,
+
+        #[structopt(
+            long = "output",
+            possible_values = &OutputFormat::variants(),
+            requires = "dry-run",
+            help = "Format of the plan printed by --dry-run [default: text]"
+        )]
+        output: Option<OutputFormat>,
+
+        #[structopt(
+            long = "allow-outside-root",
+            help = "Allow writing and deleting files outside the project root"
+        )]
+        allow_outside_root: bool,
+
+        #[structopt(
+            long = "no-strict",
+            help = "Render undefined variables as empty strings and allow any file name"
+        )]
+        no_strict: bool,
+
+        #[structopt(flatten)]
+        root_opts: RootOpts,
+
+        #[structopt(flatten)]
+        variable_opts: VariableOpts,
+    },
+    #[structopt(about = "Revert the most recent generate, delete or rename")]
+    Undo {
+        #[structopt(help = "Operation id shown by --list")]
+        id: Option<u64>,
+
+        #[structopt(long = "list", help = "Show recorded operations")]
+        list: bool,
+
+        #[structopt(
+            long = "force",
+            help = "Undo even if files are modified after the operation"
+        )]
+        force: bool,
+
+        #[structopt(
+            long = "allow-outside-root",
+            help = "Allow writing and deleting files outside the project root"
+        )]
+        allow_outside_root: bool,
+
+        #[structopt(flatten)]
+        root_opts: RootOpts,
     },
 }
 
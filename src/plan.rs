@@ -0,0 +1,481 @@
+use crate::error::MdmgError;
+use crate::front_matter::{self, FrontMatter};
+use crate::journal::hash;
+use crate::markdown::parse;
+use crate::scaffold::Scaffold;
+use crate::template::{render, MdmgCtx, Template};
+use crate::template_repository::TemplateRepository;
+use crate::Result;
+
+use serde_json::Value;
+use std::path::{Component, Path};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedPlan {
+    pub plan_hash: String,
+    pub front_matter: FrontMatter,
+    pub ctx: MdmgCtx,
+    pub scaffolds: Vec<Scaffold>,
+}
+
+/// A part of a rendered plan split at the `## @include plan` directives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Section {
+    Markdown(String),
+    Include(String),
+}
+
+fn split_includes(markdown: &str) -> Vec<Section> {
+    let mut sections = vec![];
+    let mut current = String::new();
+    let mut in_code_block = false;
+    for line in markdown.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+        }
+        match trimmed.strip_prefix("## @include ") {
+            Some(plan_name) if !in_code_block => {
+                if !current.is_empty() {
+                    sections.push(Section::Markdown(std::mem::take(&mut current)));
+                }
+                sections.push(Section::Include(plan_name.trim().to_string()));
+            }
+            _ => current.push_str(line),
+        }
+    }
+    if !current.is_empty() {
+        sections.push(Section::Markdown(current));
+    }
+    sections
+}
+
+/// A part of a plan template split at the `## @each list as name` directives.
+/// The directive repeats the next section, from its heading to the next heading.
+/// `line` is the number of lines of the template before the part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Chunk {
+    Template {
+        body: String,
+        line: usize,
+    },
+    Each {
+        list: String,
+        name: String,
+        body: String,
+        line: usize,
+    },
+}
+
+fn split_loops(template: &str) -> Vec<Chunk> {
+    let mut chunks = vec![];
+    let mut current = String::new();
+    let mut current_line = 0;
+    // The directive of the section being read and whether its heading is read.
+    let mut each: Option<(String, String, bool)> = None;
+    let mut in_code_block = false;
+
+    fn flush(
+        chunks: &mut Vec<Chunk>,
+        current: &mut String,
+        line: usize,
+        each: Option<(String, String, bool)>,
+    ) {
+        let body = std::mem::take(current);
+        match each {
+            Some((list, name, _)) => chunks.push(Chunk::Each {
+                list,
+                name,
+                body,
+                line,
+            }),
+            None if !body.is_empty() => chunks.push(Chunk::Template { body, line }),
+            None => {}
+        }
+    }
+
+    for (index, line) in template.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+        }
+        let is_heading = !in_code_block && (trimmed == "##" || trimmed.starts_with("## "));
+        if let Some(directive) = trimmed.strip_prefix("## @each ").filter(|_| is_heading) {
+            flush(&mut chunks, &mut current, current_line, each.take());
+            current_line = index + 1;
+            let words = directive.split_whitespace().collect::<Vec<&str>>();
+            let name = match words.as_slice() {
+                [_, "as", name, ..] => name,
+                _ => "item",
+            };
+            each = Some((words[0].to_string(), name.to_string(), false));
+            continue;
+        }
+        match each.as_mut() {
+            Some((_, _, heading_is_read)) if is_heading && !*heading_is_read => {
+                *heading_is_read = true
+            }
+            Some(_) if is_heading => {
+                flush(&mut chunks, &mut current, current_line, each.take());
+                current_line = index;
+            }
+            _ => {}
+        }
+        current.push_str(line);
+    }
+    flush(&mut chunks, &mut current, current_line, each);
+    chunks
+}
+
+/// Render the plan template, repeating the sections of `## @each` directives for each element of the list.
+fn render_loops(template: Template, ctx: &MdmgCtx) -> Result<String> {
+    split_loops(template.body())
+        .into_iter()
+        .map(|chunk| match chunk {
+            Chunk::Template { body, line } => render(template.part(body, line), ctx),
+            Chunk::Each {
+                list,
+                name,
+                body,
+                line,
+            } => {
+                if name == "identify" {
+                    return Err(MdmgError::ReservedVariable(name));
+                }
+                let elements = match ctx.variables.get(&list) {
+                    Some(Value::Array(elements)) => elements.clone(),
+                    _ => return Err(MdmgError::EachIsNotList(list)),
+                };
+                elements
+                    .into_iter()
+                    .map(|element| {
+                        let mut ctx = ctx.clone();
+                        ctx.variables.insert(name.clone(), element);
+                        // The last section of a trimmed plan has no newline to separate the repetitions.
+                        let mut rendered = render(template.part(body.clone(), line), &ctx)?;
+                        if !rendered.ends_with('\n') {
+                            rendered.push('\n');
+                        }
+                        Ok(rendered)
+                    })
+                    .collect::<Result<String>>()
+            }
+        })
+        .collect()
+}
+
+/// Later scaffolds override the earlier ones with the same path, keeping the earlier position.
+fn merge(scaffolds: Vec<Scaffold>) -> Vec<Scaffold> {
+    let mut merged: Vec<Scaffold> = vec![];
+    for scaffold in scaffolds {
+        match merged
+            .iter_mut()
+            .find(|merged| merged.file_name() == scaffold.file_name())
+        {
+            Some(merged) => *merged = scaffold,
+            None => merged.push(scaffold),
+        }
+    }
+    merged
+}
+
+fn compose(
+    template_repository: &dyn TemplateRepository,
+    plan_name: &str,
+    ctx: MdmgCtx,
+    stack: &mut Vec<String>,
+) -> Result<RenderedPlan> {
+    if stack.iter().any(|name| name == plan_name) {
+        let mut cycle = stack.clone();
+        cycle.push(plan_name.to_string());
+        return Err(MdmgError::PlanCycle(cycle));
+    }
+    stack.push(plan_name.to_string());
+
+    let template = template_repository.resolve(plan_name.to_string())?;
+    let plan_hash = hash(template.body().as_bytes());
+    let (front_matter, template) = front_matter::extract(template)?;
+    let ctx = front_matter.apply(ctx)?;
+
+    let mut scaffolds = vec![];
+    for parent in front_matter.extends.iter() {
+        scaffolds.extend(compose(template_repository, parent, ctx.clone(), stack)?.scaffolds);
+    }
+    let rendered = render_loops(template, &ctx).map_err(|e| match e {
+        MdmgError::TempalteRenderError {
+            reason,
+            location: None,
+        } => MdmgError::TempalteRenderError {
+            reason: format!("{} in plan {}", reason, plan_name),
+            location: None,
+        },
+        e => e,
+    })?;
+    for section in split_includes(&rendered) {
+        match section {
+            Section::Markdown(markdown) => scaffolds.extend(parse(markdown)?),
+            Section::Include(name) => {
+                scaffolds.extend(compose(template_repository, &name, ctx.clone(), stack)?.scaffolds)
+            }
+        }
+    }
+    stack.pop();
+
+    Ok(RenderedPlan {
+        plan_hash,
+        front_matter,
+        ctx,
+        scaffolds: merge(scaffolds),
+    })
+}
+
+/// Returns the file names that are empty, absolute or go up with `..`.
+fn invalid_file_names(scaffolds: &[Scaffold]) -> Vec<String> {
+    scaffolds
+        .iter()
+        .map(|scaffold| scaffold.file_name())
+        .filter(|file_name| {
+            let path = Path::new(file_name);
+            file_name.trim().is_empty()
+                || path.has_root()
+                || path.is_absolute()
+                || path
+                    .components()
+                    .any(|component| component == Component::ParentDir)
+        })
+        .map(|file_name| file_name.to_string())
+        .collect()
+}
+
+/// Resolve the plan with the plans it extends or includes, validate the context against the front matter
+/// and render them into scaffolds. In strict mode, file names must be relative paths inside the project.
+pub fn render_plan(
+    template_repository: &dyn TemplateRepository,
+    plan_name: &str,
+    ctx: MdmgCtx,
+) -> Result<RenderedPlan> {
+    let plan = compose(template_repository, plan_name, ctx, &mut vec![])?;
+    let invalid_file_names = invalid_file_names(&plan.scaffolds);
+    if plan.ctx.strict && !invalid_file_names.is_empty() {
+        return Err(MdmgError::InvalidFileNames(invalid_file_names));
+    }
+    Ok(plan)
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg(test)]
+mod tests {
+    use super::{render_plan, split_includes, split_loops, Chunk, Section};
+    use crate::error::MdmgError;
+    use crate::file::FileName;
+    use crate::scaffold::Scaffold;
+    use crate::template::{MdmgCtx, Template};
+    use crate::template_repository::TemplateRepository;
+    use indoc::indoc;
+    use std::collections::HashMap;
+
+    struct StubTemplateRepository(HashMap<&'static str, &'static str>);
+
+    impl TemplateRepository for StubTemplateRepository {
+        fn list(&self) -> crate::Result<Vec<FileName>> {
+            unimplemented!()
+        }
+        fn resolve(&self, template_name: String) -> crate::Result<Template> {
+            self.0
+                .get(template_name.as_str())
+                .map(|body| Template::new(*body))
+                .ok_or(MdmgError::TemplateIsNotFound(template_name))
+        }
+    }
+
+    fn scaffold(file_name: &str, file_body: &str) -> Scaffold {
+        Scaffold::Complete {
+            file_name: file_name.to_string(),
+            file_body: file_body.to_string(),
+        }
+    }
+
+    #[test]
+    fn split_includes_ignores_code_blocks() {
+        let markdown = "## foo.md\n```\n## @include bar\n```\n## @include baz\n## qux.md\n";
+        assert_eq!(
+            split_includes(markdown),
+            vec![
+                Section::Markdown("## foo.md\n```\n## @include bar\n```\n".to_string()),
+                Section::Include("baz".to_string()),
+                Section::Markdown("## qux.md\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn render_plan_merges_extended_and_included_plans() {
+        let repository = StubTemplateRepository(HashMap::from([
+            (
+                "component",
+                indoc! {"
+                    ## {{identify}}.tsx
+
+                    ```
+                    component
+                    ```
+
+                    ## {{identify}}.css
+
+                    ```
+                    style
+                    ```
+                "},
+            ),
+            (
+                "story",
+                indoc! {"
+                    ## {{identify}}.stories.tsx
+
+                    ```
+                    story
+                    ```
+                "},
+            ),
+            (
+                "component-with-story",
+                indoc! {"
+                    ---
+                    extends: component
+                    ---
+                    ## {{identify}}.css
+
+                    ```
+                    overridden
+                    ```
+
+                    ## @include story
+                "},
+            ),
+        ]));
+
+        let actual = render_plan(&repository, "component-with-story", MdmgCtx::new("foo")).unwrap();
+        assert_eq!(
+            actual.scaffolds,
+            vec![
+                scaffold("foo.tsx", "component\n"),
+                scaffold("foo.css", "overridden\n"),
+                scaffold("foo.stories.tsx", "story\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn render_plan_rejects_file_names_outside_project_in_strict_mode() {
+        let repository = StubTemplateRepository(HashMap::from([(
+            "escape",
+            "## /etc/{{identify}}\n\n```\nfoo\n```\n## ../{{identify}}.md\n\n```\nfoo\n```\n## src/{{identify}}.md\n\n```\nfoo\n```\n",
+        )]));
+
+        match render_plan(&repository, "escape", MdmgCtx::new("foo")) {
+            Err(MdmgError::InvalidFileNames(file_names)) => {
+                assert_eq!(file_names, vec!["/etc/foo", "../foo.md"])
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(
+            render_plan(
+                &repository,
+                "escape",
+                MdmgCtx::new("foo").with_strict(false)
+            )
+            .unwrap()
+            .scaffolds
+            .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn render_plan_returning_cycle_error() {
+        let repository = StubTemplateRepository(HashMap::from([
+            (
+                "foo",
+                "---\nextends: [bar]\n---\n## foo.md\n\n```\nfoo\n```\n",
+            ),
+            ("bar", "## @include foo\n"),
+        ]));
+
+        match render_plan(&repository, "foo", MdmgCtx::new("foo")) {
+            Err(MdmgError::PlanCycle(cycle)) => assert_eq!(cycle, vec!["foo", "bar", "foo"]),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn split_loops_returning_repeated_sections() {
+        let template =
+            "## foo.md\n## @each tables as table\n## {{table}}.sql\n```\n## bar\n```\n## baz.md\n";
+        assert_eq!(
+            split_loops(template),
+            vec![
+                Chunk::Template {
+                    body: "## foo.md\n".to_string(),
+                    line: 0
+                },
+                Chunk::Each {
+                    list: "tables".to_string(),
+                    name: "table".to_string(),
+                    body: "## {{table}}.sql\n```\n## bar\n```\n".to_string(),
+                    line: 2,
+                },
+                Chunk::Template {
+                    body: "## baz.md\n".to_string(),
+                    line: 6
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn render_plan_expands_each_sections() {
+        let repository = StubTemplateRepository(HashMap::from([(
+            "migration",
+            indoc! {"
+                ## @each tables
+                ## {{identify}}/{{item}}.sql
+
+                ```
+                CREATE TABLE {{item}};
+                ```
+
+                ## {{identify}}/schema.sql
+
+                ```
+                schema
+                ```
+
+                ## @each tables
+                ## {{identify}}/{{item}}.rs
+
+                ```
+                {{item}}
+                ```"},
+        )]));
+
+        let mut ctx = MdmgCtx::new("db");
+        ctx.variables
+            .insert("tables".to_string(), serde_json::json!(["users", "posts"]));
+        assert_eq!(
+            render_plan(&repository, "migration", ctx)
+                .unwrap()
+                .scaffolds,
+            vec![
+                scaffold("db/users.sql", "CREATE TABLE users;\n"),
+                scaffold("db/posts.sql", "CREATE TABLE posts;\n"),
+                scaffold("db/schema.sql", "schema\n"),
+                scaffold("db/users.rs", "users\n"),
+                scaffold("db/posts.rs", "posts\n"),
+            ]
+        );
+        assert!(matches!(
+            render_plan(&repository, "migration", MdmgCtx::new("db")),
+            Err(MdmgError::EachIsNotList(_))
+        ));
+    }
+}
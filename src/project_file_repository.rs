@@ -0,0 +1,64 @@
+use crate::Result;
+
+use derive_more::Constructor;
+use ignore::WalkBuilder;
+use std::path::PathBuf;
+
+/// Files of the project that are not ignored by `.gitignore`.
+pub trait ProjectFileRepository {
+    fn list(&self) -> Result<Vec<String>>;
+}
+
+#[derive(Debug, Clone, Constructor)]
+pub struct FSProjectFileRepository {
+    root: PathBuf,
+}
+
+impl ProjectFileRepository for FSProjectFileRepository {
+    fn list(&self) -> Result<Vec<String>> {
+        // Hidden files such as `.git` and `.mdmg` are skipped.
+        let files = WalkBuilder::new(&self.root)
+            .require_git(false)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_file())
+            })
+            .filter_map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(&self.root)
+                    .ok()
+                    .map(|path| path.to_string_lossy().to_string())
+            })
+            .collect::<Vec<String>>();
+        Ok(files)
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg(test)]
+mod tests {
+    use super::{FSProjectFileRepository, ProjectFileRepository};
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::path::Path;
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn fs_project_file_repository_list_respects_gitignore() {
+        let base = Path::new("./support/fs_project_file_repository_list_respects_gitignore");
+        assert!(create_dir_all(base.join("src")).is_ok());
+        assert!(create_dir_all(base.join("target")).is_ok());
+        assert!(create_dir_all(base.join(".mdmg")).is_ok());
+        assert!(write(base.join(".gitignore"), "/target\n").is_ok());
+        assert!(write(base.join("src/foo.rs"), "foo").is_ok());
+        assert!(write(base.join("target/foo.rs"), "foo").is_ok());
+        assert!(write(base.join(".mdmg/foo.md"), "foo").is_ok());
+
+        let repository = FSProjectFileRepository::new(base.to_path_buf());
+        assert_eq!(repository.list().unwrap(), vec!["src/foo.rs".to_string()]);
+        remove_dir_all(base).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+use crate::error::MdmgError;
+use crate::Result;
+
+use std::env::{current_dir, set_current_dir};
+use std::path::{absolute, Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+static WORKING_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Find the nearest directory that has `.mdmg`, walking up from `start` like git finds `.git`.
+pub fn find_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|directory| directory.join(".mdmg").is_dir())
+        .map(Path::to_path_buf)
+}
+
+/// Resolve the project root. An explicit root is used as it is, otherwise the nearest `.mdmg` is
+/// searched. The current directory is used when there is no `.mdmg`, for the plans in the XDG data directory.
+pub fn resolve_root(root: Option<PathBuf>) -> Result<PathBuf> {
+    match root {
+        Some(root) if root.is_dir() => Ok(absolute(root)?),
+        Some(root) => Err(MdmgError::RootIsNotDirectory(
+            root.to_string_lossy().to_string(),
+        )),
+        None => {
+            let current_dir = current_dir()?;
+            Ok(find_root(&current_dir).unwrap_or(current_dir))
+        }
+    }
+}
+
+/// Resolve the project root and move to it, so that the file names of plans are relative to the root.
+pub fn enter_root(root: Option<PathBuf>) -> Result<PathBuf> {
+    let root = resolve_root(root)?;
+    let _ = WORKING_DIR.set(current_dir()?);
+    set_current_dir(&root)?;
+    Ok(root)
+}
+
+/// The directory mdmg is run in, before moving to the project root.
+pub fn working_dir() -> Result<PathBuf> {
+    match WORKING_DIR.get() {
+        Some(working_dir) => Ok(working_dir.clone()),
+        None => Ok(current_dir()?),
+    }
+}
+
+/// Express an absolute path relative to `base`, going up with `..` when they share a directory.
+/// Relative paths and paths that share nothing but the file system root are returned as they are.
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    if path.is_relative() {
+        return path.to_path_buf();
+    }
+    let path_components = path.components().collect::<Vec<Component>>();
+    let base_components = base.components().collect::<Vec<Component>>();
+    let common = path_components
+        .iter()
+        .zip(base_components.iter())
+        .take_while(|(path_component, base_component)| path_component == base_component)
+        .count();
+    let shares_directory = path_components[..common]
+        .iter()
+        .any(|component| matches!(component, Component::Normal(_)));
+    if !shares_directory {
+        return path.to_path_buf();
+    }
+    base_components[common..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .chain(path_components[common..].iter().copied())
+        .collect()
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg(test)]
+mod tests {
+    use super::{find_root, relative_path, resolve_root};
+    use crate::error::MdmgError;
+    use std::env::current_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use std::path::Path;
+
+    #[test]
+    fn resolve_root_returning_explicit_root() {
+        assert_eq!(
+            resolve_root(Some("src".into())).unwrap(),
+            current_dir().unwrap().join("src")
+        );
+        assert!(matches!(
+            resolve_root(Some("not_exist_root".into())),
+            Err(MdmgError::RootIsNotDirectory(_))
+        ));
+    }
+
+    #[test]
+    fn relative_path_returning_path_from_base() {
+        assert_eq!(
+            relative_path(Path::new("/proj/.mdmg/foo.md"), Path::new("/proj")),
+            Path::new(".mdmg/foo.md")
+        );
+        assert_eq!(
+            relative_path(Path::new("/proj/.mdmg/foo.md"), Path::new("/proj/src/deep")),
+            Path::new("../../.mdmg/foo.md")
+        );
+        assert_eq!(
+            relative_path(Path::new("/home/.mdmg/foo.md"), Path::new("/proj")),
+            Path::new("/home/.mdmg/foo.md")
+        );
+        assert_eq!(
+            relative_path(Path::new(".mdmg/foo.md"), Path::new("/proj")),
+            Path::new(".mdmg/foo.md")
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn find_root_returning_nearest_mdmg_directory() {
+        let base = Path::new("./support/find_root_returning_nearest_mdmg_directory");
+        assert!(create_dir_all(base.join(".mdmg")).is_ok());
+        assert!(create_dir_all(base.join("src/nested/.mdmg")).is_ok());
+        assert!(create_dir_all(base.join("src/nested/deep")).is_ok());
+
+        assert_eq!(find_root(&base.join("src")), Some(base.to_path_buf()));
+        assert_eq!(
+            find_root(&base.join("src/nested/deep")),
+            Some(base.join("src/nested"))
+        );
+        assert_eq!(find_root(base), Some(base.to_path_buf()));
+        remove_dir_all(base).unwrap();
+    }
+}
@@ -1,71 +1,231 @@
+use crate::delete_executor::RemovableDirectories;
+use crate::front_matter::RenameOptions;
 use crate::generated_file_repository::GeneratedFileRepository;
+use crate::sandbox::Sandbox;
 use crate::scaffold::Scaffold;
+use crate::transaction::Transaction;
 use crate::Result;
 use crate::{error::MdmgError, logger::Logger};
 
-use derive_more::{Constructor, Display, Into};
+use derive_more::{Constructor, Display};
 use inflector::Inflector;
-use std::fs::{remove_file, rename as rename_file, write};
-use std::path::Path;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
 
 use std::sync::Arc;
 
-fn rename(rename_target: &str, before_identify: &str, after_identify: &str) -> String {
-    rename_target
-        .replace(
-            &before_identify.to_pascal_case(),
-            &after_identify.to_pascal_case(),
-        )
-        .replace(
-            &before_identify.to_camel_case(),
-            &after_identify.to_camel_case(),
-        )
-        .replace(
-            &before_identify.to_kebab_case(),
-            &after_identify.to_kebab_case(),
-        )
-        .replace(
-            &before_identify.to_snake_case(),
-            &after_identify.to_snake_case(),
-        )
+/// Pairs of the spellings of the identifies, longest first so that shorter spellings never clobber longer ones.
+fn replacements(
+    before_identify: &str,
+    after_identify: &str,
+    options: &RenameOptions,
+) -> Vec<(String, String)> {
+    let mut forms = vec![(before_identify.to_string(), after_identify.to_string())];
+    if options.plural {
+        forms.push((
+            before_identify.to_snake_case().to_plural(),
+            after_identify.to_snake_case().to_plural(),
+        ));
+        // A plural identify(`users`) also renames its singular form(`user`).
+        let singular = before_identify.to_snake_case().to_singular();
+        if singular != before_identify.to_snake_case() {
+            forms.push((singular, after_identify.to_snake_case().to_singular()));
+        }
+    }
+
+    let mut pairs: Vec<(String, String)> = vec![];
+    for (before, after) in forms.iter() {
+        for style in options.cases.iter() {
+            let before = style.convert(before);
+            if before.is_empty() || pairs.iter().any(|(known, _)| known == &before) {
+                continue;
+            }
+            pairs.push((before, style.convert(after)));
+        }
+    }
+    pairs.sort_by_key(|(before, _)| std::cmp::Reverse(before.len()));
+    pairs
+}
+
+/// A replaced spelling in a file body.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct Occurrence {
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/// Returns true when `matched` is a whole word between `previous` and `next`.
+/// A non alphanumeric character(including `_`) is a word boundary, and so is a case change(`User` in
+/// `UserService`) when `case_boundary` is set.
+fn is_word(previous: Option<char>, matched: &str, next: Option<char>, case_boundary: bool) -> bool {
+    let mut chars = matched.chars();
+    let (first, second, last) = match (chars.next(), chars.next(), matched.chars().last()) {
+        (Some(first), second, Some(last)) => (first, second, last),
+        _ => return false,
+    };
+    let starts_word = match previous {
+        None => true,
+        Some(previous) if !previous.is_alphanumeric() => true,
+        Some(_) if !case_boundary => false,
+        Some(previous) => {
+            first.is_uppercase()
+                && (!previous.is_uppercase() || second.is_some_and(|second| second.is_lowercase()))
+        }
+    };
+    let ends_word = match next {
+        None => true,
+        Some(next) if !next.is_alphanumeric() => true,
+        Some(_) if !case_boundary => false,
+        Some(next) => next.is_uppercase() && !last.is_uppercase(),
+    };
+    starts_word && ends_word
 }
 
-#[derive(Debug, Clone, Constructor, PartialEq, Eq, Default, Into)]
+fn rename(
+    rename_target: &str,
+    before_identify: &str,
+    after_identify: &str,
+    options: &RenameOptions,
+) -> (String, Vec<Occurrence>) {
+    let pairs = replacements(before_identify, after_identify, options);
+    let mut renamed = String::with_capacity(rename_target.len());
+    let mut occurrences = vec![];
+    let mut line = 1;
+    let mut previous = None;
+    let mut rest = rename_target;
+
+    while let Some(current) = rest.chars().next() {
+        let matched = pairs.iter().find(|(before, _)| {
+            rest.starts_with(before.as_str())
+                && (!options.word_boundary
+                    || is_word(
+                        previous,
+                        before,
+                        rest[before.len()..].chars().next(),
+                        options.case_boundary,
+                    ))
+        });
+        match matched {
+            Some((before, after)) => {
+                renamed.push_str(after);
+                occurrences.push(Occurrence {
+                    line,
+                    before: before.clone(),
+                    after: after.clone(),
+                });
+                line += before.matches('\n').count();
+                previous = before.chars().last();
+                rest = &rest[before.len()..];
+            }
+            None => {
+                renamed.push(current);
+                if current == '\n' {
+                    line += 1;
+                }
+                previous = Some(current);
+                rest = &rest[current.len_utf8()..];
+            }
+        }
+    }
+    (renamed, occurrences)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct ReplacementParameter {
     id: String,
     renamed_name: String,
     before_replace_body: String,
     replaced_body: String,
+    occurrences: Vec<Occurrence>,
 }
 
 impl ReplacementParameter {
+    pub fn new(
+        id: String,
+        renamed_name: String,
+        before_replace_body: String,
+        replaced_body: String,
+    ) -> Self {
+        ReplacementParameter {
+            id,
+            renamed_name,
+            before_replace_body,
+            replaced_body,
+            occurrences: vec![],
+        }
+    }
+
     pub fn from_scaffold(
         scaffold: &Scaffold,
         before_identify: &str,
         after_identify: &str,
+        options: &RenameOptions,
         generated_file_repository: Arc<dyn GeneratedFileRepository>,
     ) -> Result<ReplacementParameter> {
-        let (file_name, file_body) = match scaffold {
+        let file_name = match scaffold {
             Scaffold::Pending { file_name } => {
                 return Err(MdmgError::ReadPendingScaffoldError {
                     file_name: file_name.clone(),
                 })
             }
-            Scaffold::Complete {
-                file_name,
-                file_body,
-            } => (file_name, file_body),
+            Scaffold::Complete { file_name, .. } => file_name,
         };
+        // The file may be edited after generation, so the operation is decided from the file on disk.
         let body = generated_file_repository.resolve(Path::new(file_name))?;
-        let renamed_file_name = rename(file_name, before_identify, after_identify);
-        let replaced_file_body = rename(&body, before_identify, after_identify);
+        let (renamed_file_name, _) = rename(file_name, before_identify, after_identify, options);
+        let (replaced_file_body, occurrences) =
+            rename(&body, before_identify, after_identify, options);
+
+        Ok(ReplacementParameter {
+            occurrences,
+            ..ReplacementParameter::new(
+                file_name.clone(),
+                renamed_file_name,
+                body,
+                replaced_file_body,
+            )
+        })
+    }
+
+    /// Replace the identify in a file that is not generated by the plan. The file name is kept.
+    pub fn from_reference(
+        file_name: &str,
+        body: String,
+        before_identify: &str,
+        after_identify: &str,
+        options: &RenameOptions,
+    ) -> ReplacementParameter {
+        let (replaced_body, occurrences) = rename(&body, before_identify, after_identify, options);
+        ReplacementParameter {
+            id: file_name.to_string(),
+            renamed_name: file_name.to_string(),
+            before_replace_body: body,
+            replaced_body,
+            occurrences,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn renamed_name(&self) -> &str {
+        &self.renamed_name
+    }
+
+    pub fn before_replace_body(&self) -> &str {
+        &self.before_replace_body
+    }
+
+    pub fn replaced_body(&self) -> &str {
+        &self.replaced_body
+    }
 
-        Ok(ReplacementParameter::new(
-            file_name.clone(),
-            renamed_file_name,
-            file_body.clone(),
-            replaced_file_body,
-        ))
+    pub fn occurrences(&self) -> &[Occurrence] {
+        &self.occurrences
     }
 
     pub fn name_changed(&self) -> bool {
@@ -81,7 +241,8 @@ impl ReplacementParameter {
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Display)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Display, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ReplacementOperation {
     None,
     Rename,
@@ -94,6 +255,7 @@ pub trait ReplacementOperationInterpreter {
     fn rename(&self, from_name: &str, to_name: &str) -> Result<()>;
     fn replace(&self, id: &str, replaced_body: &str) -> Result<()>;
     fn rename_and_replace(&self, parameter: &ReplacementParameter) -> Result<()>;
+    fn remove_dir(&self, path: &str) -> Result<()>;
 }
 
 fn run(
@@ -119,6 +281,8 @@ fn run(
 #[derive(Constructor, Clone)]
 pub struct FSReplacementOperationInterpreter {
     logger_instance: Arc<dyn Logger>,
+    transaction: Arc<dyn Transaction>,
+    sandbox: Sandbox,
 }
 
 impl ReplacementOperationInterpreter for FSReplacementOperationInterpreter {
@@ -129,8 +293,14 @@ impl ReplacementOperationInterpreter for FSReplacementOperationInterpreter {
     fn rename(&self, from_name: &str, to_name: &str) -> Result<()> {
         self.logger_instance
             .info(format!("{} rename started.(to: {})", &from_name, &to_name).as_str());
+        self.sandbox.check(from_name)?;
+        self.sandbox.check(to_name)?;
 
-        rename_file(from_name, to_name)?;
+        if let Some(parent) = Path::new(to_name).parent() {
+            self.transaction.create_dir_all(parent)?;
+        }
+        self.transaction
+            .rename(Path::new(from_name), Path::new(to_name))?;
 
         self.logger_instance
             .info(format!("{} renamed", &from_name).as_str());
@@ -140,8 +310,10 @@ impl ReplacementOperationInterpreter for FSReplacementOperationInterpreter {
     fn replace(&self, id: &str, replaced_body: &str) -> Result<()> {
         self.logger_instance
             .info(format!("{} replace file body started.", &id).as_str());
+        self.sandbox.check(id)?;
 
-        write(id, replaced_body)?;
+        self.transaction
+            .write(Path::new(id), replaced_body.as_bytes())?;
 
         self.logger_instance
             .info(format!("{} replaced file body.", &id).as_str());
@@ -156,12 +328,21 @@ impl ReplacementOperationInterpreter for FSReplacementOperationInterpreter {
             )
             .as_str(),
         );
+        self.sandbox.check(&parameter.id)?;
+        self.sandbox.check(&parameter.renamed_name)?;
 
-        write(
-            parameter.renamed_name.as_str(),
-            parameter.replaced_body.as_str(),
+        if let Some(parent) = Path::new(parameter.renamed_name.as_str()).parent() {
+            self.transaction.create_dir_all(parent)?;
+        }
+        // Move the file first so that the permissions are kept.
+        self.transaction.rename(
+            Path::new(parameter.id.as_str()),
+            Path::new(parameter.renamed_name.as_str()),
+        )?;
+        self.transaction.write(
+            Path::new(parameter.renamed_name.as_str()),
+            parameter.replaced_body.as_bytes(),
         )?;
-        remove_file(Path::new(parameter.id.as_str()))?;
 
         self.logger_instance.info(
             format!(
@@ -172,6 +353,35 @@ impl ReplacementOperationInterpreter for FSReplacementOperationInterpreter {
         );
         Ok(())
     }
+    fn remove_dir(&self, path: &str) -> Result<()> {
+        self.sandbox.check(path)?;
+        self.transaction.remove_dir(Path::new(path))?;
+
+        self.logger_instance
+            .info(format!("{} is empty and removed.", &path).as_str());
+
+        Ok(())
+    }
+}
+
+/// Writes nothing, so that the executor only plans the rename.
+#[derive(Debug, Clone, Copy, Default, Constructor)]
+pub struct DryRunReplacementOperationInterpreter {}
+
+impl ReplacementOperationInterpreter for DryRunReplacementOperationInterpreter {
+    fn none(&self, _id: &str) {}
+    fn rename(&self, _from_name: &str, _to_name: &str) -> Result<()> {
+        Ok(())
+    }
+    fn replace(&self, _id: &str, _replaced_body: &str) -> Result<()> {
+        Ok(())
+    }
+    fn rename_and_replace(&self, _parameter: &ReplacementParameter) -> Result<()> {
+        Ok(())
+    }
+    fn remove_dir(&self, _path: &str) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl From<&ReplacementParameter> for ReplacementOperation {
@@ -188,13 +398,114 @@ impl From<&ReplacementParameter> for ReplacementOperation {
     }
 }
 
+/// A directory moved as a unit because the identify appears in its name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DirectoryMove {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RenameResult {
+    pub parameters: Vec<ReplacementParameter>,
+    pub moved_directories: Vec<DirectoryMove>,
+}
+
+/// Directories whose names are changed, shallowest first.
+/// `from` of a nested directory is placed in the already moved parent.
+fn directory_moves(parameters: &[ReplacementParameter]) -> Vec<DirectoryMove> {
+    let mut moves: Vec<DirectoryMove> = vec![];
+    for parameter in parameters.iter() {
+        let from = Path::new(&parameter.id)
+            .parent()
+            .map(|parent| parent.components().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let to = Path::new(&parameter.renamed_name)
+            .parent()
+            .map(|parent| parent.components().collect::<Vec<_>>())
+            .unwrap_or_default();
+        // The directory structure itself is changed(e.g. path case), then files are moved one by one.
+        if from.len() != to.len() {
+            continue;
+        }
+        let mut current = PathBuf::new();
+        for (from_segment, to_segment) in from.iter().zip(to.iter()) {
+            if from_segment != to_segment {
+                let directory_move = DirectoryMove {
+                    from: current.join(from_segment),
+                    to: current.join(to_segment),
+                };
+                if !moves.contains(&directory_move) {
+                    moves.push(directory_move);
+                }
+            }
+            current.push(to_segment);
+        }
+    }
+    moves.sort_by_key(|directory_move| directory_move.from.components().count());
+    moves
+}
+
+/// Targets that would overwrite other files. Every target is checked so that all conflicts are reported at once.
+fn find_conflicts(parameters: &[ReplacementParameter]) -> Vec<String> {
+    let mut conflicts = vec![];
+    for (index, parameter) in parameters.iter().enumerate() {
+        if let Some(other) = parameters[..index]
+            .iter()
+            .find(|other| other.renamed_name == parameter.renamed_name)
+        {
+            conflicts.push(format!(
+                "{} is the target of both {} and {}",
+                parameter.renamed_name, other.id, parameter.id
+            ));
+        } else if parameter.name_changed() && Path::new(&parameter.renamed_name).exists() {
+            conflicts.push(format!(
+                "{} already exists (renamed from {})",
+                parameter.renamed_name, parameter.id
+            ));
+        }
+    }
+    for directory_move in directory_moves(parameters) {
+        if directory_move.to.exists() && !directory_move.to.is_dir() {
+            conflicts.push(format!(
+                "{} already exists and is not a directory (renamed from {})",
+                directory_move.to.to_string_lossy(),
+                directory_move.from.to_string_lossy()
+            ));
+        }
+    }
+    conflicts
+}
+
+/// Where the file is placed after the directories are moved.
+fn moved_path(path: &str, moved_directories: &[DirectoryMove]) -> String {
+    moved_directories
+        .iter()
+        .fold(PathBuf::from(path), |path, directory_move| {
+            match path.strip_prefix(&directory_move.from) {
+                Ok(rest) => directory_move.to.join(rest),
+                Err(_) => path,
+            }
+        })
+        .to_string_lossy()
+        .to_string()
+}
+
 pub trait RenameExecutor {
     fn execute(
         &self,
         scaffolds: &[Scaffold],
         before_identify: &str,
         after_identify: &str,
-    ) -> Result<()>;
+        options: &RenameOptions,
+    ) -> Result<RenameResult>;
+    fn update_references(
+        &self,
+        files: &[String],
+        before_identify: &str,
+        after_identify: &str,
+        options: &RenameOptions,
+    ) -> Result<Vec<ReplacementParameter>>;
 }
 
 #[derive(Constructor, Clone)]
@@ -203,73 +514,349 @@ pub struct DefaultRenameExecutor {
     generated_file_repository: Arc<dyn GeneratedFileRepository>,
 }
 
-impl RenameExecutor for DefaultRenameExecutor {
-    fn execute(
+impl DefaultRenameExecutor {
+    /// Compute every replacement before touching the file system, and fail with all conflicts found.
+    fn plan(
         &self,
         scaffolds: &[Scaffold],
         before_identify: &str,
         after_identify: &str,
-    ) -> Result<()> {
+        options: &RenameOptions,
+    ) -> Result<Vec<ReplacementParameter>> {
+        let mut parameters = vec![];
+        let mut conflicts = vec![];
         for scaffold in scaffolds.iter() {
-            let parameter = ReplacementParameter::from_scaffold(
+            match ReplacementParameter::from_scaffold(
                 scaffold,
                 before_identify,
                 after_identify,
+                options,
                 self.generated_file_repository.clone(),
-            )?;
-            run(&parameter, self.interpreter.as_ref())?;
+            ) {
+                Ok(parameter) => parameters.push(parameter),
+                Err(MdmgError::GeneratedFileIsNotFound(_)) => {
+                    conflicts.push(format!("{} is not found", scaffold.file_name()))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        conflicts.extend(find_conflicts(&parameters));
+        if conflicts.is_empty() {
+            Ok(parameters)
+        } else {
+            Err(MdmgError::RenameConflicts(conflicts))
+        }
+    }
+
+    /// Remove the old directories left empty by the rename, deepest first.
+    fn remove_emptied_directories(&self, parameters: &[ReplacementParameter]) -> Result<()> {
+        let mut directories = parameters
+            .iter()
+            .flat_map(|parameter| Path::new(&parameter.id).ancestors().skip(1))
+            .filter(|directory| RemovableDirectories::UnderRoot.contains(directory))
+            .collect::<BTreeSet<&Path>>()
+            .into_iter()
+            .collect::<Vec<&Path>>();
+        directories.sort_by_key(|directory| std::cmp::Reverse(directory.components().count()));
+        for directory in directories {
+            if read_dir(directory).is_ok_and(|mut entries| entries.next().is_none()) {
+                self.interpreter.remove_dir(&directory.to_string_lossy())?;
+            }
         }
         Ok(())
     }
 }
 
+impl RenameExecutor for DefaultRenameExecutor {
+    fn execute(
+        &self,
+        scaffolds: &[Scaffold],
+        before_identify: &str,
+        after_identify: &str,
+        options: &RenameOptions,
+    ) -> Result<RenameResult> {
+        let parameters = self.plan(scaffolds, before_identify, after_identify, options)?;
+
+        let mut moved_directories = vec![];
+        for directory_move in directory_moves(&parameters) {
+            // An existing directory can not be replaced, then the files are moved one by one.
+            if directory_move.from.is_dir() && !directory_move.to.exists() {
+                self.interpreter.rename(
+                    &directory_move.from.to_string_lossy(),
+                    &directory_move.to.to_string_lossy(),
+                )?;
+                moved_directories.push(directory_move);
+            }
+        }
+
+        for parameter in parameters.iter() {
+            let moved = ReplacementParameter {
+                id: moved_path(&parameter.id, &moved_directories),
+                ..parameter.clone()
+            };
+            run(&moved, self.interpreter.as_ref())?;
+        }
+        self.remove_emptied_directories(&parameters)?;
+
+        Ok(RenameResult {
+            parameters,
+            moved_directories,
+        })
+    }
+
+    fn update_references(
+        &self,
+        files: &[String],
+        before_identify: &str,
+        after_identify: &str,
+        options: &RenameOptions,
+    ) -> Result<Vec<ReplacementParameter>> {
+        files
+            .iter()
+            // Binary and unreadable files are not references.
+            .filter_map(|file| {
+                self.generated_file_repository
+                    .resolve(Path::new(file))
+                    .ok()
+                    .map(|body| (file, body))
+            })
+            .map(|(file, body)| {
+                ReplacementParameter::from_reference(
+                    file,
+                    body,
+                    before_identify,
+                    after_identify,
+                    options,
+                )
+            })
+            .filter(|parameter| parameter.body_changed())
+            .map(|parameter| {
+                run(&parameter, self.interpreter.as_ref())?;
+                Ok(parameter)
+            })
+            .collect()
+    }
+}
+
 #[cfg(not(tarpaulin_include))]
 #[cfg(test)]
+#[allow(clippy::arc_with_non_send_sync)]
 mod tests {
-    use crate::generated_file_repository::GeneratedFileRepository;
+    use crate::case_style::CaseStyle;
+    use crate::error::MdmgError;
+    use crate::front_matter::RenameOptions;
+    use crate::generated_file_repository::{FSGeneratedFileRepository, GeneratedFileRepository};
     use crate::logger::Logger;
     use crate::rename_executor::{
         DefaultRenameExecutor, FSReplacementOperationInterpreter, RenameExecutor,
         ReplacementOperationInterpreter,
     };
+    use crate::sandbox::Sandbox;
     use crate::scaffold::Scaffold;
+    use crate::transaction::FSTransaction;
+    use std::env::current_dir;
 
-    use super::{rename, run, ReplacementOperation, ReplacementParameter};
+    use super::{
+        directory_moves, find_conflicts, moved_path, rename, replacements, run, DirectoryMove,
+        Occurrence, ReplacementOperation, ReplacementParameter,
+    };
     use derive_more::{Constructor, Deref, DerefMut};
     use std::cell::{Cell, RefCell};
-    use std::fs::{create_dir, read_to_string, remove_dir, remove_file, write};
-    use std::path::Path;
+    use std::fs::{
+        create_dir, create_dir_all, read_to_string, remove_dir, remove_dir_all, remove_file, write,
+    };
+    use std::path::{Path, PathBuf};
     use std::sync::{Arc, Mutex};
 
     #[test]
     fn test_rename() {
+        // The identify is a part of these identifiers, so the case change must be a word boundary.
+        let options = RenameOptions {
+            case_boundary: true,
+            ..RenameOptions::default()
+        };
         assert_eq!(
-            rename("ExampleService", "Example", "Himanoa"),
+            rename("ExampleService", "Example", "Himanoa", &options).0,
             "HimanoaService".to_string(),
             "Pascal case test"
         );
         assert_eq!(
-            rename("exampleService", "example", "himanoa"),
+            rename("exampleService", "example", "himanoa", &options).0,
             "himanoaService".to_string(),
             "Camel case test"
         );
         assert_eq!(
-            rename("example-service", "example", "himanoa"),
+            rename("example-service", "example", "himanoa", &options).0,
             "himanoa-service".to_string(),
             "Kebab case test"
         );
         assert_eq!(
-            rename("example_service", "example", "himanoa"),
+            rename("example_service", "example", "himanoa", &options).0,
             "himanoa_service".to_string(),
             "Snake case test"
         );
         assert_eq!(
-            rename("example_service", "adfadf", "himanoa"),
+            rename("example_service", "adfadf", "himanoa", &options).0,
             "example_service".to_string(),
             "No replace"
         );
     }
 
+    #[test]
+    fn test_rename_all_case_styles() {
+        let options = RenameOptions::default();
+        assert_eq!(
+            rename(
+                "const FOO_BAR_MAX: usize; // Foo-Bar, Foo Bar, Foo bar, foo.bar, foo/bar",
+                "foo_bar",
+                "baz_qux",
+                &options
+            )
+            .0,
+            "const BAZ_QUX_MAX: usize; // Baz-Qux, Baz Qux, Baz qux, baz.qux, baz/qux",
+        );
+        assert_eq!(
+            rename(
+                "let fooBars: Vec<FooBar> = FOO_BARS;",
+                "foo_bar",
+                "category",
+                &options
+            )
+            .0,
+            "let categories: Vec<Category> = CATEGORIES;",
+            "Plural form"
+        );
+        assert_eq!(
+            rename(
+                "Foo FooBar",
+                "foo",
+                "foo_bar",
+                &RenameOptions {
+                    case_boundary: true,
+                    ..RenameOptions::default()
+                }
+            )
+            .0,
+            "FooBar FooBarBar",
+            "Replaced text is not replaced again"
+        );
+    }
+
+    #[test]
+    fn test_rename_with_options() {
+        let options = RenameOptions {
+            cases: vec![CaseStyle::Pascal],
+            plural: false,
+            word_boundary: true,
+            case_boundary: false,
+        };
+        assert_eq!(
+            rename("FooBar foo_bar FooBars", "foo_bar", "baz", &options).0,
+            "Baz foo_bar FooBars"
+        );
+    }
+
+    #[test]
+    fn test_rename_only_whole_words() {
+        let options = RenameOptions::default();
+        assert_eq!(
+            rename(
+                "user username superuser UserService SuperUser USER_ID SUPERUSER users",
+                "user",
+                "account",
+                &options
+            )
+            .0,
+            "account username superuser UserService SuperUser ACCOUNT_ID SUPERUSER accounts"
+        );
+        assert_eq!(
+            rename(
+                "user UserService SuperUser USER_ID username",
+                "user",
+                "account",
+                &RenameOptions {
+                    case_boundary: true,
+                    ..RenameOptions::default()
+                }
+            )
+            .0,
+            "account AccountService SuperAccount ACCOUNT_ID username"
+        );
+        assert_eq!(
+            rename(
+                "user username superuser",
+                "user",
+                "account",
+                &RenameOptions {
+                    word_boundary: false,
+                    ..RenameOptions::default()
+                }
+            )
+            .0,
+            "account accountname superaccount"
+        );
+    }
+
+    #[test]
+    fn test_rename_returning_occurrences() {
+        let (_, occurrences) = rename(
+            "struct FooBar;\n\nimpl FooBar {}\nconst FOO_BAR: usize = 1;\n",
+            "foo_bar",
+            "baz",
+            &RenameOptions::default(),
+        );
+        assert_eq!(
+            occurrences,
+            vec![
+                Occurrence {
+                    line: 1,
+                    before: "FooBar".to_string(),
+                    after: "Baz".to_string()
+                },
+                Occurrence {
+                    line: 3,
+                    before: "FooBar".to_string(),
+                    after: "Baz".to_string()
+                },
+                Occurrence {
+                    line: 4,
+                    before: "FOO_BAR".to_string(),
+                    after: "BAZ".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replacements_is_ordered_longest_first() {
+        let actual = replacements("user", "member", &RenameOptions::default());
+        assert_eq!(
+            actual,
+            vec![
+                ("Users".to_string(), "Members".to_string()),
+                ("users".to_string(), "members".to_string()),
+                ("USERS".to_string(), "MEMBERS".to_string()),
+                ("User".to_string(), "Member".to_string()),
+                ("user".to_string(), "member".to_string()),
+                ("USER".to_string(), "MEMBER".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rename_plural_identify() {
+        assert_eq!(
+            rename(
+                "let users: Vec<User> = USERS; fn user_name(user: &User) {}",
+                "users",
+                "accounts",
+                &RenameOptions::default()
+            )
+            .0,
+            "let accounts: Vec<Account> = ACCOUNTS; fn account_name(account: &Account) {}"
+        );
+    }
+
     #[test]
     fn test_replacement_parameter_name_changed() {
         assert!(
@@ -334,7 +921,8 @@ mod tests {
                 before_replace_body: "foo".to_string(),
                 replaced_body: "bar".to_string(),
                 id: "foo".to_string(),
-                renamed_name: "bar".to_string()
+                renamed_name: "bar".to_string(),
+                ..ReplacementParameter::default()
             }
             .all_changed(),
             "before_replace_body not equal replaced_body"
@@ -371,7 +959,8 @@ mod tests {
                 before_replace_body: "foo".to_string(),
                 replaced_body: "bar".to_string(),
                 id: "foo".to_string(),
-                renamed_name: "bar".to_string()
+                renamed_name: "bar".to_string(),
+                ..ReplacementParameter::default()
             }),
             ReplacementOperation::RenameAndReplace,
             "all_changed"
@@ -396,6 +985,9 @@ mod tests {
             fn rename_and_replace(&self, _parameter: &ReplacementParameter) -> crate::Result<()> {
                 unreachable!()
             }
+            fn remove_dir(&self, _path: &str) -> crate::Result<()> {
+                unreachable!()
+            }
         }
 
         let interpreter = Dummy(Cell::new(false));
@@ -431,6 +1023,9 @@ mod tests {
             fn rename_and_replace(&self, _parameter: &ReplacementParameter) -> crate::Result<()> {
                 unreachable!()
             }
+            fn remove_dir(&self, _path: &str) -> crate::Result<()> {
+                unreachable!()
+            }
         }
 
         let interpreter = Dummy(Cell::new(false));
@@ -466,6 +1061,9 @@ mod tests {
             fn rename_and_replace(&self, _parameter: &ReplacementParameter) -> crate::Result<()> {
                 unreachable!()
             }
+            fn remove_dir(&self, _path: &str) -> crate::Result<()> {
+                unreachable!()
+            }
         }
 
         let interpreter = Dummy(Cell::new(false));
@@ -501,6 +1099,9 @@ mod tests {
                 self.0.replace(true);
                 Ok(())
             }
+            fn remove_dir(&self, _path: &str) -> crate::Result<()> {
+                unreachable!()
+            }
         }
 
         let interpreter = Dummy(Cell::new(false));
@@ -519,6 +1120,10 @@ mod tests {
 
     #[test]
     fn test_from_scafold() {
+        let options = RenameOptions {
+            word_boundary: false,
+            ..RenameOptions::default()
+        };
         let scaffold = Scaffold::Complete {
             file_name: "sooo".to_string(),
             file_body: "".to_string(),
@@ -537,6 +1142,7 @@ mod tests {
             &scaffold,
             "before_identify",
             "after_identify",
+            &options,
             Arc::new(DummyGeneratedFileRepository::new("".to_string())),
         )
         .unwrap();
@@ -552,6 +1158,7 @@ mod tests {
             &scaffold,
             "so",
             "af",
+            &options,
             Arc::new(DummyGeneratedFileRepository::new("".to_string())),
         )
         .unwrap();
@@ -567,6 +1174,7 @@ mod tests {
             &scaffold,
             "so",
             "af",
+            &options,
             Arc::new(DummyGeneratedFileRepository::new("so".to_string())),
         )
         .unwrap();
@@ -582,11 +1190,27 @@ mod tests {
             &scaffold,
             "so",
             "af",
+            &options,
             Arc::new(DummyGeneratedFileRepository::new("so".to_string())),
         )
         .unwrap();
         assert!(parameter.all_changed());
 
+        // The file is edited after generation, but the identify does not appear.
+        let scaffold = Scaffold::Complete {
+            file_name: "ooo".to_string(),
+            file_body: "so".to_string(),
+        };
+        let parameter = ReplacementParameter::from_scaffold(
+            &scaffold,
+            "so",
+            "af",
+            &options,
+            Arc::new(DummyGeneratedFileRepository::new("edited".to_string())),
+        )
+        .unwrap();
+        assert!(!parameter.body_changed());
+
         let scaffold = Scaffold::Pending {
             file_name: "xxx".to_string(),
         };
@@ -594,6 +1218,7 @@ mod tests {
             &scaffold,
             "so",
             "af",
+            &options,
             Arc::new(DummyGeneratedFileRepository::new("so".to_string()))
         )
         .is_err())
@@ -611,7 +1236,11 @@ mod tests {
     #[cfg_attr(not(feature = "fs-test"), ignore)]
     pub fn test_fs_replacement_operation_interpreter_none() {
         let logger = Arc::new(DummyLogger(Cell::new(false)));
-        let interpreter = FSReplacementOperationInterpreter::new(logger.clone());
+        let interpreter = FSReplacementOperationInterpreter::new(
+            logger.clone(),
+            Arc::new(FSTransaction::new()),
+            Sandbox::new(current_dir().unwrap(), false),
+        );
         interpreter.none("foo");
         assert!(logger.0.get());
     }
@@ -623,7 +1252,11 @@ mod tests {
             Path::new("./support/fs_rename_executor_fs_replacement_operation_interpreter_rename/");
         let file_path = path.join("dummy.txt");
         let logger = Arc::new(DummyLogger(Cell::new(false)));
-        let interpreter = FSReplacementOperationInterpreter::new(logger.clone());
+        let interpreter = FSReplacementOperationInterpreter::new(
+            logger.clone(),
+            Arc::new(FSTransaction::new()),
+            Sandbox::new(current_dir().unwrap(), false),
+        );
         let dist_path =
             "./support/fs_rename_executor_fs_replacement_operation_interpreter_rename/bar.txt";
 
@@ -645,7 +1278,11 @@ mod tests {
             Path::new("./support/fs_rename_executor_fs_replacement_operation_interpreter_replace/");
         let file_path = path.join("dummy.txt");
         let logger = Arc::new(DummyLogger(Cell::new(false)));
-        let interpreter = FSReplacementOperationInterpreter::new(logger.clone());
+        let interpreter = FSReplacementOperationInterpreter::new(
+            logger.clone(),
+            Arc::new(FSTransaction::new()),
+            Sandbox::new(current_dir().unwrap(), false),
+        );
 
         assert!(create_dir(path).is_ok());
         assert!(write(&file_path, "dummy").is_ok());
@@ -666,7 +1303,11 @@ mod tests {
         );
         let file_path = path.join("dummy");
         let logger = Arc::new(DummyLogger(Cell::new(false)));
-        let interpreter = FSReplacementOperationInterpreter::new(logger.clone());
+        let interpreter = FSReplacementOperationInterpreter::new(
+            logger.clone(),
+            Arc::new(FSTransaction::new()),
+            Sandbox::new(current_dir().unwrap(), false),
+        );
         let dist_path = Path::new("./support/fs_rename_executor_fs_replacement_operation_interpreter_rename_and_replace/dummy1");
 
         assert!(create_dir(path).is_ok());
@@ -680,12 +1321,76 @@ mod tests {
             ))
             .is_ok());
         assert!(Path::new(&dist_path).exists());
-        assert_eq!(read_to_string(&dist_path).unwrap().as_str(), "dummy1");
+        assert_eq!(read_to_string(dist_path).unwrap().as_str(), "dummy1");
         assert!(logger.0.get());
         assert!(remove_file(dist_path).is_ok());
         assert!(remove_dir(path).is_ok());
     }
 
+    #[test]
+    pub fn rename_executor_update_references() {
+        #[derive(DerefMut, Deref)]
+        struct DummyInterpreter(pub Mutex<Vec<String>>);
+
+        impl ReplacementOperationInterpreter for DummyInterpreter {
+            fn none(&self, _id: &str) {
+                unreachable!()
+            }
+            fn rename(&self, _from_name: &str, _to_name: &str) -> crate::Result<()> {
+                unreachable!()
+            }
+            fn replace(&self, id: &str, replaced_body: &str) -> crate::Result<()> {
+                self.lock()
+                    .unwrap()
+                    .push(format!("{}: {}", id, replaced_body));
+                Ok(())
+            }
+            fn rename_and_replace(&self, _parameter: &ReplacementParameter) -> crate::Result<()> {
+                unreachable!()
+            }
+            fn remove_dir(&self, _path: &str) -> crate::Result<()> {
+                unreachable!()
+            }
+        }
+
+        struct DummyGeneratedFileRepository;
+
+        impl GeneratedFileRepository for DummyGeneratedFileRepository {
+            fn resolve(&self, file_name: &std::path::Path) -> crate::Result<String> {
+                match file_name.to_str() {
+                    Some("src/main.ts") => Ok("import Foo from './Foo'".to_string()),
+                    Some("src/other.ts") => Ok("import Other from './Other'".to_string()),
+                    _ => Err(MdmgError::GeneratedFileIsNotFound(
+                        file_name.to_string_lossy().to_string(),
+                    )),
+                }
+            }
+        }
+
+        let interpreter = Arc::new(DummyInterpreter(Mutex::new(vec![])));
+        let executor =
+            DefaultRenameExecutor::new(interpreter.clone(), Arc::new(DummyGeneratedFileRepository));
+        let files = ["src/main.ts", "src/other.ts", "image.png"]
+            .iter()
+            .map(|file| file.to_string())
+            .collect::<Vec<String>>();
+
+        let actual = executor
+            .update_references(&files, "foo", "bar", &RenameOptions::default())
+            .unwrap();
+        assert_eq!(
+            actual
+                .iter()
+                .map(|parameter| parameter.id())
+                .collect::<Vec<&str>>(),
+            vec!["src/main.ts"]
+        );
+        assert_eq!(
+            interpreter.lock().unwrap().clone(),
+            vec!["src/main.ts: import Bar from './Bar'".to_string()]
+        );
+    }
+
     #[test]
     pub fn rename_executor_execute() {
         #[derive(DerefMut, Deref)]
@@ -707,6 +1412,9 @@ mod tests {
                 self.lock().unwrap().push("rename_and_replace".to_string());
                 Ok(())
             }
+            fn remove_dir(&self, _path: &str) -> crate::Result<()> {
+                unreachable!()
+            }
         }
 
         #[derive(Constructor)]
@@ -738,11 +1446,11 @@ mod tests {
                 file_body: "xxx".to_string(),
             },
             Scaffold::Complete {
-                file_name: "xxx".to_string(),
+                file_name: "yyy".to_string(),
                 file_body: "replace_target".to_string(),
             },
             Scaffold::Complete {
-                file_name: "replace_target".to_string(),
+                file_name: "replace_target.rs".to_string(),
                 file_body: "replace_target".to_string(),
             },
         ];
@@ -760,8 +1468,195 @@ mod tests {
             ))),
         );
         assert!(executor
-            .execute(&scaffolds, "replace_target", "replaced")
+            .execute(
+                &scaffolds,
+                "replace_target",
+                "replaced",
+                &RenameOptions::default()
+            )
             .is_ok());
         assert_eq!(interpreter.lock().unwrap().clone(), expected_plans);
     }
+
+    #[test]
+    fn directory_moves_returning_shallowest_first() {
+        let parameters = vec![
+            ReplacementParameter::new(
+                "src/foo/foo_view/index.ts".to_string(),
+                "src/bar/bar_view/index.ts".to_string(),
+                "".to_string(),
+                "".to_string(),
+            ),
+            ReplacementParameter::new(
+                "src/foo/mod.rs".to_string(),
+                "src/bar/mod.rs".to_string(),
+                "".to_string(),
+                "".to_string(),
+            ),
+            ReplacementParameter::new(
+                "foo/baz.rs".to_string(),
+                "bar/baz.rs".to_string(),
+                "".to_string(),
+                "".to_string(),
+            ),
+            ReplacementParameter::new(
+                "foo_baz/qux.rs".to_string(),
+                "foo/baz/qux.rs".to_string(),
+                "".to_string(),
+                "".to_string(),
+            ),
+        ];
+        let actual = directory_moves(&parameters);
+        assert_eq!(
+            actual
+                .iter()
+                .map(|directory_move| (
+                    directory_move.from.to_string_lossy().to_string(),
+                    directory_move.to.to_string_lossy().to_string()
+                ))
+                .collect::<Vec<(String, String)>>(),
+            vec![
+                ("foo".to_string(), "bar".to_string()),
+                ("src/foo".to_string(), "src/bar".to_string()),
+                (
+                    "src/bar/foo_view".to_string(),
+                    "src/bar/bar_view".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            moved_path("src/foo/foo_view/index.ts", &actual),
+            "src/bar/bar_view/index.ts"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    pub fn rename_executor_execute_moves_directories() {
+        let base = Path::new("./support/rename_executor_execute_moves_directories");
+        assert!(create_dir_all(base.join("src/foo_bar")).is_ok());
+        assert!(create_dir_all(base.join("docs/foo")).is_ok());
+        assert!(write(base.join("src/foo_bar/mod.rs"), "struct FooBar;").is_ok());
+        assert!(write(base.join("src/foo_bar/extra.rs"), "extra").is_ok());
+        assert!(write(base.join("docs/foo/bar.md"), "foo/bar").is_ok());
+        let scaffolds = ["src/foo_bar/mod.rs", "docs/foo/bar.md"]
+            .iter()
+            .map(|file_name| Scaffold::Complete {
+                file_name: base.join(file_name).to_string_lossy().to_string(),
+                file_body: "".to_string(),
+            })
+            .collect::<Vec<Scaffold>>();
+        let options = RenameOptions {
+            cases: vec![CaseStyle::Pascal, CaseStyle::Snake, CaseStyle::Path],
+            ..RenameOptions::default()
+        };
+
+        let executor = DefaultRenameExecutor::new(
+            Arc::new(FSReplacementOperationInterpreter::new(
+                Arc::new(DummyLogger(Cell::new(false))),
+                Arc::new(FSTransaction::new()),
+                Sandbox::new(current_dir().unwrap(), false),
+            )),
+            Arc::new(FSGeneratedFileRepository::new(PathBuf::from("."))),
+        );
+        let actual = executor
+            .execute(&scaffolds, "foo_bar", "baz", &options)
+            .unwrap();
+
+        assert_eq!(
+            actual.moved_directories,
+            vec![DirectoryMove {
+                from: base.join("src/foo_bar"),
+                to: base.join("src/baz"),
+            }]
+        );
+        assert_eq!(
+            read_to_string(base.join("src/baz/mod.rs")).unwrap(),
+            "struct Baz;"
+        );
+        assert!(base.join("src/baz/extra.rs").exists());
+        assert!(!base.join("src/foo_bar").exists());
+        // The directory structure is changed by the path case, then the emptied directory is removed.
+        assert_eq!(read_to_string(base.join("docs/baz.md")).unwrap(), "baz");
+        assert!(!base.join("docs/foo").exists());
+        remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    fn find_conflicts_returning_duplicate_targets() {
+        let parameters = vec![
+            ReplacementParameter::new(
+                "support/foo.rs".to_string(),
+                "support/bar.rs".to_string(),
+                "".to_string(),
+                "".to_string(),
+            ),
+            ReplacementParameter::new(
+                "support/bar.rs".to_string(),
+                "support/bar.rs".to_string(),
+                "".to_string(),
+                "".to_string(),
+            ),
+            ReplacementParameter::new(
+                "support/baz.rs".to_string(),
+                "support/baz.rs".to_string(),
+                "".to_string(),
+                "".to_string(),
+            ),
+        ];
+        assert_eq!(
+            find_conflicts(&parameters),
+            vec![
+                "support/bar.rs is the target of both support/foo.rs and support/bar.rs"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    pub fn rename_executor_execute_refuses_conflicts() {
+        let base = Path::new("./support/rename_executor_execute_refuses_conflicts");
+        assert!(create_dir_all(base).is_ok());
+        assert!(write(base.join("foo.rs"), "foo").is_ok());
+        assert!(write(base.join("bar.rs"), "bar").is_ok());
+        let scaffolds = ["foo.rs", "foo_view.rs"]
+            .iter()
+            .map(|file_name| Scaffold::Complete {
+                file_name: base.join(file_name).to_string_lossy().to_string(),
+                file_body: "".to_string(),
+            })
+            .collect::<Vec<Scaffold>>();
+
+        let executor = DefaultRenameExecutor::new(
+            Arc::new(FSReplacementOperationInterpreter::new(
+                Arc::new(DummyLogger(Cell::new(false))),
+                Arc::new(FSTransaction::new()),
+                Sandbox::new(current_dir().unwrap(), false),
+            )),
+            Arc::new(FSGeneratedFileRepository::new(PathBuf::from("."))),
+        );
+        let actual = executor.execute(&scaffolds, "foo", "bar", &RenameOptions::default());
+
+        match actual {
+            Err(MdmgError::RenameConflicts(conflicts)) => assert_eq!(
+                conflicts,
+                vec![
+                    format!(
+                        "{} is not found",
+                        base.join("foo_view.rs").to_string_lossy()
+                    ),
+                    format!(
+                        "{} already exists (renamed from {})",
+                        base.join("bar.rs").to_string_lossy(),
+                        base.join("foo.rs").to_string_lossy()
+                    ),
+                ]
+            ),
+            _ => unreachable!(),
+        }
+        assert_eq!(read_to_string(base.join("foo.rs")).unwrap(), "foo");
+        assert_eq!(read_to_string(base.join("bar.rs")).unwrap(), "bar");
+        remove_dir_all(base).unwrap();
+    }
 }
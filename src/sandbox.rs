@@ -0,0 +1,108 @@
+use crate::error::MdmgError;
+use crate::Result;
+
+use std::path::{absolute, Component, Path, PathBuf};
+
+/// Where the executors may write, move and delete files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sandbox {
+    /// Paths must stay inside the project root after resolving `..` and symbolic links.
+    Root(PathBuf),
+    /// Any path is allowed, given by `--allow-outside-root`.
+    Unrestricted,
+}
+
+impl Sandbox {
+    pub fn new(root: PathBuf, allow_outside_root: bool) -> Self {
+        if allow_outside_root {
+            Sandbox::Unrestricted
+        } else {
+            Sandbox::Root(root)
+        }
+    }
+
+    /// Fails when the path escapes the project root.
+    pub fn check<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let root = match self {
+            Sandbox::Root(root) => root,
+            Sandbox::Unrestricted => return Ok(()),
+        };
+        let path = path.as_ref();
+        let outside_root = || MdmgError::OutsideRoot {
+            path: path.to_string_lossy().to_string(),
+            root: root.to_string_lossy().to_string(),
+        };
+        let root = root.canonicalize()?;
+        match resolve(path) {
+            Some(resolved) if resolved.starts_with(&root) => Ok(()),
+            _ => Err(outside_root()),
+        }
+    }
+}
+
+/// Resolve `..` and the symbolic links of the existing part of the path.
+/// Returns `None` when the path goes through a broken symbolic link.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in absolute(path).ok()?.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => resolved.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+                if resolved.symlink_metadata().is_ok() {
+                    resolved = resolved.canonicalize().ok()?;
+                }
+            }
+        }
+    }
+    Some(resolved)
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg(test)]
+mod tests {
+    use super::Sandbox;
+    use crate::error::MdmgError;
+    use std::env::current_dir;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use std::path::Path;
+
+    #[test]
+    fn sandbox_check_rejects_paths_outside_root() {
+        let sandbox = Sandbox::new(current_dir().unwrap(), false);
+
+        assert!(sandbox.check("src/foo.rs").is_ok());
+        assert!(sandbox.check("new_dir/../src/foo.rs").is_ok());
+        assert!(sandbox.check(current_dir().unwrap().join("foo.rs")).is_ok());
+        assert!(matches!(
+            sandbox.check("../foo.rs"),
+            Err(MdmgError::OutsideRoot { .. })
+        ));
+        assert!(sandbox.check("new_dir/../../foo.rs").is_err());
+        assert!(sandbox.check("/etc/passwd").is_err());
+        assert!(Sandbox::new(current_dir().unwrap(), true)
+            .check("/etc/passwd")
+            .is_ok());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn sandbox_check_rejects_symlinks_out_of_root() {
+        let base = Path::new("./support/sandbox_check_rejects_symlinks_out_of_root");
+        assert!(create_dir_all(base).is_ok());
+        assert!(std::os::unix::fs::symlink("/tmp", base.join("outside")).is_ok());
+        assert!(std::os::unix::fs::symlink("../../src", base.join("inside")).is_ok());
+        assert!(std::os::unix::fs::symlink("/nonexistent/mdmg", base.join("broken")).is_ok());
+
+        let sandbox = Sandbox::new(current_dir().unwrap(), false);
+        assert!(sandbox.check(base.join("outside/foo.rs")).is_err());
+        assert!(sandbox.check(base.join("inside/foo.rs")).is_ok());
+        assert!(sandbox.check(base.join("broken")).is_err());
+        remove_dir_all(base).unwrap();
+    }
+}
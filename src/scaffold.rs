@@ -8,3 +8,12 @@ pub enum Scaffold {
         file_name: String,
     },
 }
+
+impl Scaffold {
+    pub fn file_name(&self) -> &str {
+        match self {
+            Scaffold::Complete { file_name, .. } => file_name,
+            Scaffold::Pending { file_name } => file_name,
+        }
+    }
+}
@@ -1,25 +1,118 @@
+use crate::error::MdmgError;
+use crate::sandbox::Sandbox;
 use crate::scaffold::Scaffold;
+use crate::snapshot_repository::SnapshotRepository;
+use crate::transaction::Transaction;
 use crate::Result;
 
-use std::fs::{create_dir_all, File};
-use std::io::Write;
-use std::path::Path;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 
-use derive_more::Constructor;
+use derive_more::{Constructor, Display};
 use yansi::Paint;
 
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Display)]
+pub enum ConflictStrategy {
+    #[display(fmt = "skip")]
+    Skip,
+    #[display(fmt = "overwrite")]
+    Overwrite,
+    #[display(fmt = "error")]
+    Error,
+    #[display(fmt = "backup")]
+    Backup,
+    #[display(fmt = "merge")]
+    Merge,
+}
+
+impl ConflictStrategy {
+    pub fn variants() -> [&'static str; 5] {
+        ["skip", "overwrite", "error", "backup", "merge"]
+    }
+}
+
+impl FromStr for ConflictStrategy {
+    type Err = MdmgError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(ConflictStrategy::Skip),
+            "overwrite" => Ok(ConflictStrategy::Overwrite),
+            "error" => Ok(ConflictStrategy::Error),
+            "backup" => Ok(ConflictStrategy::Backup),
+            "merge" => Ok(ConflictStrategy::Merge),
+            _ => Err(MdmgError::InvalidConflictStrategy(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum ExecutionResult {
+    Generated,
+    Ignored,
+    Skipped,
+    Overwritten,
+    BackedUp,
+    Merged,
+    MergedWithConflicts,
+}
+
 pub trait ScaffoldExecutor {
-    fn execute(self, scaffold: &Scaffold) -> Result<()>;
+    fn execute(&self, scaffold: &Scaffold) -> Result<ExecutionResult>;
 }
 
 #[derive(Clone, Debug, Copy, Constructor)]
 pub struct DryRunScaffoldExecutor {}
 
-#[derive(Clone, Debug, Copy, Constructor)]
-pub struct FSScaffoldExecutor {}
+#[derive(Clone, Constructor)]
+pub struct FSScaffoldExecutor {
+    conflict_strategy: ConflictStrategy,
+    snapshot_repository: Arc<dyn SnapshotRepository>,
+    transaction: Arc<dyn Transaction>,
+    sandbox: Sandbox,
+}
+
+/// Where `--on-conflict backup` moves the existing file.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut backup_path = path.as_os_str().to_os_string();
+    backup_path.push(".orig");
+    PathBuf::from(backup_path)
+}
+
+/// Check that the file can be written to the path before anything is written.
+pub fn validate_destination(path: &Path) -> Result<()> {
+    let invalid = |reason: &str| MdmgError::InvalidDestination {
+        path: path.to_string_lossy().to_string(),
+        reason: reason.to_string(),
+    };
+    if path.is_dir() {
+        return Err(invalid("destination is a directory"));
+    }
+    let exist_ancestor = path
+        .ancestors()
+        .skip(1)
+        .map(|ancestor| match ancestor.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => ancestor,
+        })
+        .find(|ancestor| ancestor.exists());
+    match exist_ancestor {
+        Some(ancestor) if !ancestor.is_dir() => Err(invalid(&format!(
+            "{} is not a directory",
+            ancestor.to_string_lossy()
+        ))),
+        Some(ancestor) if ancestor.metadata()?.permissions().readonly() => Err(invalid(&format!(
+            "{} is read only",
+            ancestor.to_string_lossy()
+        ))),
+        _ => Ok(()),
+    }
+}
 
 impl ScaffoldExecutor for DryRunScaffoldExecutor {
-    fn execute(self, scaffold: &Scaffold) -> Result<()> {
+    fn execute(&self, scaffold: &Scaffold) -> Result<ExecutionResult> {
         if let Scaffold::Complete {
             file_name,
             file_body,
@@ -29,44 +122,163 @@ impl ScaffoldExecutor for DryRunScaffoldExecutor {
             println!("{}", file_body);
             println!("====================");
         }
-        Ok(())
+        Ok(ExecutionResult::Generated)
     }
 }
 
-impl ScaffoldExecutor for FSScaffoldExecutor {
-    fn execute(self, scaffold: &Scaffold) -> Result<()> {
-        if let Scaffold::Complete {
-            file_name,
-            file_body,
-        } = scaffold
-        {
-            if Path::new(file_name).exists() {
+impl FSScaffoldExecutor {
+    fn write(&self, path: &Path, body: &str) -> Result<()> {
+        if let Some(parent_path) = path.parent() {
+            self.transaction.create_dir_all(parent_path)?;
+        }
+        self.transaction.write(path, body.as_bytes())
+    }
+
+    fn resolve_conflict(&self, path: &Path, file_body: &str) -> Result<ExecutionResult> {
+        let file_name = path.to_string_lossy();
+        match self.conflict_strategy {
+            ConflictStrategy::Skip => {
                 println!(
                     "{} {} (file_exists)",
                     Paint::yellow("Skip generate:"),
                     file_name
                 );
-                return Ok(());
+                Ok(ExecutionResult::Skipped)
             }
-            let parent = Path::new(file_name).parent();
-            if let Some(parent_path) = parent {
-                create_dir_all(parent_path)?;
+            ConflictStrategy::Error => {
+                Err(MdmgError::ConflictingFiles(vec![file_name.to_string()]))
             }
-            let mut file = File::create(file_name)?;
-            file.write_all(file_body.as_bytes())?;
-            println!("{} {}", Paint::green("Generated:"), file_name);
+            ConflictStrategy::Overwrite => {
+                self.write(path, file_body)?;
+                println!("{} {}", Paint::green("Overwritten:"), file_name);
+                Ok(ExecutionResult::Overwritten)
+            }
+            ConflictStrategy::Backup => {
+                // Never overwrite the backup of an earlier run.
+                let backup_path = backup_path(path);
+                self.sandbox.check(&backup_path)?;
+                if backup_path.symlink_metadata().is_ok() {
+                    return Err(MdmgError::ConflictingFiles(vec![backup_path
+                        .to_string_lossy()
+                        .to_string()]));
+                }
+                self.transaction.rename(path, &backup_path)?;
+                self.write(path, file_body)?;
+                println!(
+                    "{} {} (backup: {})",
+                    Paint::green("Overwritten:"),
+                    file_name,
+                    backup_path.to_string_lossy()
+                );
+                Ok(ExecutionResult::BackedUp)
+            }
+            ConflictStrategy::Merge => {
+                // Without the last generated version there is no base to merge against.
+                let Some(base) = self.snapshot_repository.resolve(path)? else {
+                    println!(
+                        "{} {} (no snapshot of the last generated version to merge against)",
+                        Paint::yellow("Skip generate:"),
+                        file_name
+                    );
+                    return Ok(ExecutionResult::Skipped);
+                };
+                let current = read_to_string(path)?;
+                match diffy::merge(&base, &current, file_body) {
+                    Ok(merged) => {
+                        self.write(path, &merged)?;
+                        println!("{} {}", Paint::green("Merged:"), file_name);
+                        Ok(ExecutionResult::Merged)
+                    }
+                    Err(conflicted) => {
+                        self.write(path, &conflicted)?;
+                        println!(
+                            "{} {} (conflict markers are written)",
+                            Paint::yellow("Merged with conflicts:"),
+                            file_name
+                        );
+                        Ok(ExecutionResult::MergedWithConflicts)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ScaffoldExecutor for FSScaffoldExecutor {
+    fn execute(&self, scaffold: &Scaffold) -> Result<ExecutionResult> {
+        let (file_name, file_body) = match scaffold {
+            Scaffold::Complete {
+                file_name,
+                file_body,
+            } => (file_name, file_body),
+            Scaffold::Pending { .. } => return Ok(ExecutionResult::Ignored),
+        };
+        let path = Path::new(file_name);
+        self.sandbox.check(path)?;
+
+        if path.exists() {
+            return self.resolve_conflict(path, file_body);
         }
-        Ok(())
+        self.write(path, file_body)?;
+        println!("{} {}", Paint::green("Generated:"), file_name);
+        Ok(ExecutionResult::Generated)
     }
 }
 
 #[cfg(not(tarpaulin_include))]
 #[cfg(test)]
+#[allow(clippy::arc_with_non_send_sync)]
 mod tests {
-    use super::{DryRunScaffoldExecutor, FSScaffoldExecutor, ScaffoldExecutor};
+    use super::{
+        backup_path, validate_destination, ConflictStrategy, DryRunScaffoldExecutor,
+        ExecutionResult, FSScaffoldExecutor, ScaffoldExecutor,
+    };
+    use crate::sandbox::Sandbox;
     use crate::scaffold::Scaffold;
+    use crate::snapshot_repository::SnapshotRepository;
+    use crate::transaction::{FSTransaction, Transaction};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::env::current_dir;
     use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct StubSnapshotRepository(RefCell<HashMap<PathBuf, String>>);
+
+    impl SnapshotRepository for StubSnapshotRepository {
+        fn save(
+            &self,
+            _transaction: &dyn Transaction,
+            path: &Path,
+            body: &str,
+        ) -> crate::Result<()> {
+            self.0
+                .borrow_mut()
+                .insert(path.to_path_buf(), body.to_string());
+            Ok(())
+        }
+        fn resolve(&self, path: &Path) -> crate::Result<Option<String>> {
+            Ok(self.0.borrow().get(path).cloned())
+        }
+    }
+
+    fn executor(conflict_strategy: ConflictStrategy) -> FSScaffoldExecutor {
+        FSScaffoldExecutor::new(
+            conflict_strategy,
+            Arc::new(StubSnapshotRepository::default()),
+            Arc::new(FSTransaction::new()),
+            Sandbox::new(current_dir().unwrap(), false),
+        )
+    }
+
+    fn setup_exist_file(directory: &str, body: &str) -> String {
+        let path = format!("{}/foobar.md", directory);
+        assert!(create_dir_all(directory).is_ok());
+        assert!(write(&path, body).is_ok());
+        path
+    }
 
     #[test]
     pub fn dryrun_executor_execute_is_ok() {
@@ -77,33 +289,181 @@ mod tests {
         };
         assert!(executor.execute(&scaffold).is_ok());
     }
+
+    #[test]
+    pub fn conflict_strategy_from_str() {
+        for variant in ConflictStrategy::variants() {
+            assert_eq!(
+                variant
+                    .parse::<ConflictStrategy>()
+                    .map(|strategy| strategy.to_string())
+                    .unwrap(),
+                variant
+            );
+        }
+        assert!("unknown".parse::<ConflictStrategy>().is_err());
+    }
+
     #[test]
     #[cfg_attr(not(feature = "fs-test"), ignore)]
     pub fn fsscaffold_executor_execute_is_not_created_files_when_exiist() {
-        let executor = FSScaffoldExecutor {};
-        let path = "support/fs_scaffold_executor_execute_when_exist/foobar.md".to_string();
+        let directory = "support/fs_scaffold_executor_execute_when_exist";
+        let path = setup_exist_file(directory, "dummy");
 
-        assert!(create_dir_all(Path::new(&path).parent().unwrap()).is_ok());
-        assert!(write(&path, b"dummy").is_ok());
+        let scaffold = Scaffold::Complete {
+            file_name: path.clone(),
+            file_body: "hello_world".to_string(),
+        };
+        let actual = executor(ConflictStrategy::Skip).execute(&scaffold);
+        assert_eq!(actual.unwrap(), ExecutionResult::Skipped);
+        assert_eq!(read_to_string(path).unwrap(), "dummy".to_string());
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    pub fn fsscaffold_executor_execute_is_error_when_exist() {
+        let directory = "support/fs_scaffold_executor_execute_is_error_when_exist";
+        let path = setup_exist_file(directory, "dummy");
 
         let scaffold = Scaffold::Complete {
-            file_name: path,
+            file_name: path.clone(),
             file_body: "hello_world".to_string(),
         };
-        assert!(executor.execute(&scaffold).is_ok());
-        remove_dir_all("support/fs_scaffold_executor_execute_when_exist").unwrap();
+        assert!(executor(ConflictStrategy::Error)
+            .execute(&scaffold)
+            .is_err());
+        assert_eq!(read_to_string(path).unwrap(), "dummy".to_string());
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    pub fn fsscaffold_executor_execute_is_overwritten_when_exist() {
+        let directory = "support/fs_scaffold_executor_execute_is_overwritten_when_exist";
+        let path = setup_exist_file(directory, "dummy");
+
+        let scaffold = Scaffold::Complete {
+            file_name: path.clone(),
+            file_body: "hello_world".to_string(),
+        };
+        let actual = executor(ConflictStrategy::Overwrite).execute(&scaffold);
+        assert_eq!(actual.unwrap(), ExecutionResult::Overwritten);
+        assert_eq!(read_to_string(path).unwrap(), "hello_world".to_string());
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    pub fn fsscaffold_executor_execute_is_backed_up_when_exist() {
+        let directory = "support/fs_scaffold_executor_execute_is_backed_up_when_exist";
+        let path = setup_exist_file(directory, "dummy");
+
+        let scaffold = Scaffold::Complete {
+            file_name: path.clone(),
+            file_body: "hello_world".to_string(),
+        };
+        let actual = executor(ConflictStrategy::Backup).execute(&scaffold);
+        assert_eq!(actual.unwrap(), ExecutionResult::BackedUp);
+        assert_eq!(read_to_string(&path).unwrap(), "hello_world".to_string());
+        assert_eq!(
+            read_to_string(format!("{}.orig", path)).unwrap(),
+            "dummy".to_string()
+        );
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    pub fn fsscaffold_executor_execute_is_error_when_backup_exist() {
+        let directory = "support/fs_scaffold_executor_execute_is_error_when_backup_exist";
+        let path = setup_exist_file(directory, "dummy");
+        assert!(write(backup_path(Path::new(&path)), "first backup").is_ok());
+
+        let scaffold = Scaffold::Complete {
+            file_name: path.clone(),
+            file_body: "hello_world".to_string(),
+        };
+        assert!(executor(ConflictStrategy::Backup)
+            .execute(&scaffold)
+            .is_err());
+        assert_eq!(read_to_string(&path).unwrap(), "dummy".to_string());
+        assert_eq!(
+            read_to_string(format!("{}.orig", path)).unwrap(),
+            "first backup".to_string()
+        );
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    pub fn fsscaffold_executor_execute_is_merged_when_exist() {
+        let directory = "support/fs_scaffold_executor_execute_is_merged_when_exist";
+        let path = setup_exist_file(directory, "header\nfoo\nedited\n");
+        let executor = executor(ConflictStrategy::Merge);
+        executor
+            .snapshot_repository
+            .save(
+                executor.transaction.as_ref(),
+                Path::new(&path),
+                "header\nfoo\nbar\n",
+            )
+            .unwrap();
+
+        let scaffold = Scaffold::Complete {
+            file_name: path.clone(),
+            file_body: "new header\nfoo\nbar\n".to_string(),
+        };
+        let actual = executor.execute(&scaffold);
+        assert_eq!(actual.unwrap(), ExecutionResult::Merged);
+        assert_eq!(
+            read_to_string(&path).unwrap(),
+            "new header\nfoo\nedited\n".to_string()
+        );
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    pub fn fsscaffold_executor_execute_is_skipped_when_snapshot_is_missing() {
+        let directory = "support/fs_scaffold_executor_execute_is_skipped_when_snapshot_is_missing";
+        let path = setup_exist_file(directory, "header\nfoo\nedited\n");
+
+        let scaffold = Scaffold::Complete {
+            file_name: path.clone(),
+            file_body: "new header\nfoo\nbar\n".to_string(),
+        };
+        let actual = executor(ConflictStrategy::Merge).execute(&scaffold);
+        assert_eq!(actual.unwrap(), ExecutionResult::Skipped);
+        assert_eq!(
+            read_to_string(&path).unwrap(),
+            "header\nfoo\nedited\n".to_string()
+        );
+        remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    pub fn validate_destination_is_error_when_parent_is_file() {
+        let directory = "support/validate_destination_is_error_when_parent_is_file";
+        let path = setup_exist_file(directory, "dummy");
+
+        assert!(validate_destination(Path::new(&path)).is_ok());
+        assert!(validate_destination(&Path::new(&path).join("foo.md")).is_err());
+        assert!(validate_destination(Path::new(directory)).is_err());
+        remove_dir_all(directory).unwrap();
     }
 
     #[test]
     #[cfg_attr(not(feature = "fs-test"), ignore)]
     pub fn fsscaffold_executor_execute_is_created_files() {
-        let executor = FSScaffoldExecutor {};
         let path = "support/fs_scaffold_executor_execute/foobar.md".to_string();
         let scaffold = Scaffold::Complete {
             file_name: path.clone(),
             file_body: "hello_world".to_string(),
         };
-        executor.execute(&scaffold).unwrap();
+        let actual = executor(ConflictStrategy::Skip).execute(&scaffold);
+        assert_eq!(actual.unwrap(), ExecutionResult::Generated);
         let actual_file_body = read_to_string(path).expect("file is not found");
         assert_eq!(actual_file_body, "hello_world".to_string());
         remove_dir_all("support/fs_scaffold_executor_execute").unwrap();
@@ -0,0 +1,88 @@
+use crate::transaction::Transaction;
+use crate::Result;
+
+use derive_more::Constructor;
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+
+/// Keeps the last generated body of each file. It is used as the base of a three-way merge.
+pub trait SnapshotRepository {
+    /// Save through the transaction, so the snapshot is rolled back with the generated file.
+    fn save(&self, transaction: &dyn Transaction, path: &Path, body: &str) -> Result<()>;
+    /// Returns `None` when the file has no snapshot, such as a file generated before snapshots existed.
+    fn resolve(&self, path: &Path) -> Result<Option<String>>;
+}
+
+#[derive(Debug, Clone, Constructor)]
+pub struct FSSnapshotRepository {
+    base: PathBuf,
+}
+
+impl FSSnapshotRepository {
+    fn snapshot_path(&self, path: &Path) -> PathBuf {
+        path.components()
+            .fold(self.base.clone(), |acc, component| match component {
+                Component::Normal(name) => acc.join(name),
+                Component::ParentDir => acc.join("_parent_"),
+                _ => acc,
+            })
+    }
+}
+
+impl SnapshotRepository for FSSnapshotRepository {
+    fn save(&self, transaction: &dyn Transaction, path: &Path, body: &str) -> Result<()> {
+        let snapshot_path = self.snapshot_path(path);
+        if let Some(parent) = snapshot_path.parent() {
+            transaction.create_dir_all(parent)?;
+        }
+        transaction.write(&snapshot_path, body.as_bytes())
+    }
+
+    fn resolve(&self, path: &Path) -> Result<Option<String>> {
+        match read_to_string(self.snapshot_path(path)) {
+            Ok(body) => Ok(Some(body)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg(test)]
+mod tests {
+    use super::{FSSnapshotRepository, SnapshotRepository};
+    use crate::transaction::{FSTransaction, Transaction};
+    use std::fs::remove_dir_all;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn fs_snapshot_repository_snapshot_path_is_inside_base() {
+        let repository = FSSnapshotRepository::new(PathBuf::from(".mdmg/.snapshots"));
+        assert_eq!(
+            repository.snapshot_path(Path::new("src/foo.rs")),
+            PathBuf::from(".mdmg/.snapshots/src/foo.rs")
+        );
+        assert_eq!(
+            repository.snapshot_path(Path::new("/etc/../foo.rs")),
+            PathBuf::from(".mdmg/.snapshots/etc/_parent_/foo.rs")
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn fs_snapshot_repository_save_and_resolve() {
+        let base = "./support/fs_snapshot_repository_save_and_resolve";
+        let repository = FSSnapshotRepository::new(PathBuf::from(base));
+        let path = Path::new("src/foo.rs");
+
+        assert_eq!(repository.resolve(path).unwrap(), None);
+        let transaction = FSTransaction::new();
+        assert!(repository.save(&transaction, path, "hello").is_ok());
+        assert_eq!(repository.resolve(path).unwrap(), Some("hello".to_string()));
+        assert!(transaction.rollback().is_ok());
+        assert_eq!(repository.resolve(path).unwrap(), None);
+        assert!(repository.save(&transaction, path, "hello").is_ok());
+        remove_dir_all(base).unwrap();
+    }
+}
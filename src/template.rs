@@ -1,23 +1,141 @@
 use crate::error::MdmgError;
-use crate::Result;
-use handlebars::{Context, Handlebars, Helper, JsonRender, Output, RenderContext, RenderError};
+use crate::variable::Variables;
+use derive_more::Constructor;
+use handlebars::{
+    handlebars_helper, Context, Handlebars, Helper, JsonRender, JsonValue, Output, RenderContext,
+    RenderError,
+};
 use inflector::Inflector;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::env::var;
+use std::fmt;
+use std::path::Path;
 
-#[derive(Debug, Serialize, Default)]
+#[derive(Debug, Serialize, Clone, PartialEq)]
 pub struct MdmgCtx {
     pub identify: String,
+    #[serde(flatten)]
+    pub variables: Variables,
+    /// Fail on undefined variables and on file names outside the project.
+    #[serde(skip)]
+    pub strict: bool,
+}
+
+impl Default for MdmgCtx {
+    fn default() -> Self {
+        MdmgCtx::new("")
+    }
+}
+
+/// Snippets shared by the plans, keyed by the name used in `{{> name}}`.
+pub type Partials = BTreeMap<String, String>;
+
+/// Where the body of a template starts, used to point the render errors in the plan file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub path: String,
+    /// The number of lines of the plan file before the body.
+    pub line: usize,
 }
 
 #[derive(Debug, Serialize, Default, PartialEq)]
 pub struct Template {
     body: String,
+    #[serde(skip)]
+    partials: Partials,
+    #[serde(skip)]
+    origin: Origin,
 }
 
 impl Template {
     pub fn new<T: Into<String>>(body: T) -> Self {
-        Template { body: body.into() }
+        Template {
+            body: body.into(),
+            partials: Partials::new(),
+            origin: Origin::default(),
+        }
+    }
+
+    pub fn with_partials(self, partials: Partials) -> Self {
+        Template { partials, ..self }
+    }
+
+    pub fn with_origin<T: Into<String>>(self, path: T, line: usize) -> Self {
+        Template {
+            origin: Origin {
+                path: path.into(),
+                line,
+            },
+            ..self
+        }
+    }
+
+    /// A template of a part of the body starting after `line` lines, sharing the partials and the origin.
+    pub fn part<T: Into<String>>(&self, body: T, line: usize) -> Self {
+        Template::new(body)
+            .with_partials(self.partials.clone())
+            .with_origin(self.origin.path.clone(), self.origin.line + line)
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    #[cfg(test)]
+    pub fn partials(&self) -> &Partials {
+        &self.partials
+    }
+
+    /// Point the error at the line of the body, or of the partial when `partial` is given.
+    fn render_error(
+        &self,
+        reason: String,
+        partial: Option<&str>,
+        line: Option<usize>,
+        column: Option<usize>,
+    ) -> MdmgError {
+        let location = line.zip(column).map(|(line, column)| {
+            let snippet = |body: &str| body.lines().nth(line - 1).unwrap_or_default().to_string();
+            match partial.and_then(|name| self.partials.get_key_value(name)) {
+                Some((name, body)) => {
+                    TemplateLocation::new(format!("partial({})", name), line, column, snippet(body))
+                }
+                None => TemplateLocation::new(
+                    self.origin.path.clone(),
+                    self.origin.line + line,
+                    column,
+                    snippet(&self.body),
+                ),
+            }
+        });
+        MdmgError::TempalteRenderError { reason, location }
+    }
+}
+
+/// The position of a render error, displayed like a rustc diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq, Constructor)]
+pub struct TemplateLocation {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub snippet: String,
+}
+
+impl fmt::Display for TemplateLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        write!(
+            f,
+            "\n{gutter}--> {}:{}:{}\n{gutter} |\n{} | {}\n{gutter} | {}^",
+            self.path,
+            self.line,
+            self.column,
+            self.line,
+            self.snippet,
+            " ".repeat(self.column.saturating_sub(1)),
+            gutter = gutter
+        )
     }
 }
 
@@ -25,8 +143,18 @@ impl MdmgCtx {
     pub fn new<T: Into<String>>(identify: T) -> Self {
         Self {
             identify: identify.into(),
+            variables: Variables::new(),
+            strict: true,
         }
     }
+
+    pub fn with_variables(self, variables: Variables) -> Self {
+        Self { variables, ..self }
+    }
+
+    pub fn with_strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
 }
 
 fn pascal_case_helper(
@@ -93,6 +221,7 @@ fn kebab_case_helper(
     Ok(())
 }
 
+/// `{{env "NAME"}}` fails when the variable is not set unless the default is given as the second parameter.
 fn env_helper(
     h: &Helper,
     _: &Handlebars,
@@ -102,38 +231,154 @@ fn env_helper(
 ) -> std::result::Result<(), RenderError> {
     let target = h
         .param(0)
-        .ok_or_else(|| RenderError::new("Param 0 is required for kebab_case_decorator."))
+        .ok_or_else(|| RenderError::new("Param 0 is required for env."))
         .map(|s| s.value().render())?;
-    let rendered = var(&target).unwrap_or_else(|_| panic!("env({}) is not defined.", &target));
+    let rendered = match (var(&target), h.param(1)) {
+        (Ok(value), _) => value,
+        (Err(_), Some(default_value)) => default_value.value().render(),
+        (Err(_), None) => {
+            return Err(RenderError::new(format!(
+                "environment variable {} is not defined",
+                target
+            )))
+        }
+    };
     out.write(&rendered)?;
     Ok(())
 }
 
-pub fn render(template: Template, ctx: &MdmgCtx) -> Result<String> {
+fn env_or_empty_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> std::result::Result<(), RenderError> {
+    let target = h
+        .param(0)
+        .ok_or_else(|| RenderError::new("Param 0 is required for env_or_empty."))
+        .map(|s| s.value().render())?;
+    out.write(&var(&target).unwrap_or_default())?;
+    Ok(())
+}
+
+/// Partials are named after file stems, so they never conflict with the name of the plan.
+const PLAN_TEMPLATE_NAME: &str = "mdmg/plan";
+
+pub fn render(template: Template, ctx: &MdmgCtx) -> crate::Result<String> {
     let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(ctx.strict);
 
     handlebars.register_helper("pascal_case", Box::new(pascal_case_helper));
     handlebars.register_helper("camel_case", Box::new(camel_case_helper));
     handlebars.register_helper("kebab_case", Box::new(kebab_case_helper));
     handlebars.register_helper("snake_case", Box::new(snake_case_helper));
     handlebars.register_helper("env", Box::new(env_helper));
+    handlebars.register_helper("env_or_empty", Box::new(env_or_empty_helper));
+    handlebars.register_helper("plural", Box::new(plural_helper));
+    handlebars.register_helper("singular", Box::new(singular_helper));
+    handlebars.register_helper("upper", Box::new(upper_helper));
+    handlebars.register_helper("lower", Box::new(lower_helper));
+    handlebars.register_helper(
+        "screaming_snake_case",
+        Box::new(screaming_snake_case_helper),
+    );
+    handlebars.register_helper("title_case", Box::new(title_case_helper));
+    handlebars.register_helper("train_case", Box::new(train_case_helper));
+    handlebars.register_helper("replace", Box::new(replace_helper));
+    handlebars.register_helper("trim", Box::new(trim_helper));
+    handlebars.register_helper("date", Box::new(date_helper));
+    handlebars.register_helper("now", Box::new(now_helper));
+    handlebars.register_helper("uuid", Box::new(uuid_helper));
+    handlebars.register_helper("uuid_v4", Box::new(uuid_helper));
+    handlebars.register_helper("basename", Box::new(basename_helper));
+    handlebars.register_helper("dirname", Box::new(dirname_helper));
+    handlebars.register_helper("join", Box::new(join_helper));
+    handlebars.register_helper("default", Box::new(default_helper));
+    handlebars.register_helper("indent", Box::new(indent_helper));
 
+    for (name, body) in template.partials.iter() {
+        handlebars.register_partial(name, body).map_err(|e| {
+            template.render_error(e.reason().to_string(), Some(name), e.line_no, e.column_no)
+        })?;
+    }
     handlebars
-        .render_template(template.body.as_str(), ctx)
+        .register_template_string(PLAN_TEMPLATE_NAME, template.body.as_str())
+        .map_err(|e| template.render_error(e.reason().to_string(), None, e.line_no, e.column_no))?;
+
+    handlebars
+        .render(PLAN_TEMPLATE_NAME, ctx)
         .map(expand_escaped_curly_braces)
-        .map_err(|e| MdmgError::TempalteRenderError { reason: e.desc })
+        .map_err(|e| {
+            let partial = e
+                .template_name
+                .as_deref()
+                .filter(|name| *name != PLAN_TEMPLATE_NAME);
+            template.render_error(e.desc.clone(), partial, e.line_no, e.column_no)
+        })
 }
 
 fn expand_escaped_curly_braces(input: String) -> String {
     input.replace("\\{", "{").replace("\\}", "}")
 }
 
+handlebars_helper!(plural_helper: |target: str| target.to_plural());
+handlebars_helper!(singular_helper: |target: str| target.to_singular());
+handlebars_helper!(upper_helper: |target: str| target.to_uppercase());
+handlebars_helper!(lower_helper: |target: str| target.to_lowercase());
+handlebars_helper!(screaming_snake_case_helper: |target: str| target.to_screaming_snake_case());
+handlebars_helper!(title_case_helper: |target: str| target.to_title_case());
+handlebars_helper!(train_case_helper: |target: str| target.to_train_case());
+handlebars_helper!(replace_helper: |target: str, from: str, to: str| target.replace(from, to));
+handlebars_helper!(trim_helper: |target: str| target.trim());
+handlebars_helper!(uuid_helper: | | uuid::Uuid::new_v4().to_string());
+handlebars_helper!(basename_helper: |path: str| Path::new(path)
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default());
+handlebars_helper!(dirname_helper: |path: str| Path::new(path)
+    .parent()
+    .map(|parent| parent.to_string_lossy().to_string())
+    .unwrap_or_default());
+handlebars_helper!(join_helper: |list: array, separator: str| list
+    .iter()
+    .map(|value| value.render())
+    .collect::<Vec<String>>()
+    .join(separator));
+// `*args` does not fail on the missing value in strict mode.
+handlebars_helper!(default_helper: |*args| {
+    let value = args.first().copied().unwrap_or(&JsonValue::Null);
+    let default_value = args.get(1).copied().unwrap_or(&JsonValue::Null);
+    match value {
+        JsonValue::Null => default_value.clone(),
+        JsonValue::String(s) if s.is_empty() => default_value.clone(),
+        value => value.clone(),
+    }
+});
+handlebars_helper!(indent_helper: |target: str, width: u64| target
+    .split_inclusive('\n')
+    .map(|line| if line.trim().is_empty() {
+        line.to_string()
+    } else {
+        format!("{}{}", " ".repeat(width as usize), line)
+    })
+    .collect::<String>());
+// `date` and `now` take an optional chrono format string.
+handlebars_helper!(date_helper: |*args| chrono::Local::now()
+    .format(args.first().and_then(|format| format.as_str()).unwrap_or("%Y-%m-%d"))
+    .to_string());
+handlebars_helper!(now_helper: |*args| chrono::Local::now()
+    .format(args.first().and_then(|format| format.as_str()).unwrap_or("%Y-%m-%dT%H:%M:%S%:z"))
+    .to_string());
+
 #[cfg(not(tarpaulin_include))]
 #[cfg(test)]
 mod tests {
     use crate::template::expand_escaped_curly_braces;
 
     use super::*;
+    use indoc::indoc;
+    use serde_json::json;
     use std::default::Default;
     use std::env::{remove_var, set_var};
 
@@ -183,6 +428,88 @@ mod tests {
         assert_eq!(actual.unwrap(), "foo_adapter")
     }
 
+    #[test]
+    fn render_returning_env_default_and_error() {
+        remove_var("MDMG_TEST_VALUE3");
+        assert_eq!(
+            render(
+                Template::new("{{env \"MDMG_TEST_VALUE3\" \"fallback\"}}[{{env_or_empty \"MDMG_TEST_VALUE3\"}}]"),
+                &MdmgCtx::new("himanoa"),
+            )
+            .unwrap(),
+            "fallback[]"
+        );
+        match render(
+            Template::new("{{env \"MDMG_TEST_VALUE3\"}}"),
+            &MdmgCtx::new("himanoa"),
+        ) {
+            Err(MdmgError::TempalteRenderError { reason, .. }) => {
+                assert_eq!(
+                    reason,
+                    "environment variable MDMG_TEST_VALUE3 is not defined"
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn render_returning_error_location() {
+        let template = Template::new("## foo.md\n\n```\n{{pascal_cas identify}}\n```")
+            .with_origin(".mdmg/foo.md", 3);
+        match render(template, &MdmgCtx::new("foo")) {
+            Err(e @ MdmgError::TempalteRenderError { .. }) => assert_eq!(
+                e.to_string(),
+                indoc! {r#"
+                    failed render the template: Helper not defined: "pascal_cas"
+                     --> .mdmg/foo.md:7:1
+                      |
+                    7 | {{pascal_cas identify}}
+                      | ^"#}
+            ),
+            _ => unreachable!(),
+        }
+
+        let mut partials = Partials::new();
+        partials.insert("header".to_string(), "// {{#if}}".to_string());
+        match render(
+            Template::new("{{> header}}").with_partials(partials),
+            &MdmgCtx::new("foo"),
+        ) {
+            Err(MdmgError::TempalteRenderError {
+                location: Some(location),
+                ..
+            }) => assert_eq!(location.path, "partial(header)"),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn render_is_error_on_undefined_variable_in_strict_mode() {
+        match render(Template::new("src/{{identfy}}.rs"), &MdmgCtx::new("foo")) {
+            Err(MdmgError::TempalteRenderError { reason, .. }) => {
+                assert_eq!(reason, "Variable \"identfy\" not found in strict mode.")
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(
+            render(
+                Template::new("src/{{identfy}}.rs{{#if with_tests}} tested{{/if}}"),
+                &MdmgCtx::new("foo").with_strict(false)
+            )
+            .unwrap(),
+            "src/.rs"
+        );
+        assert_eq!(
+            render(
+                Template::new("{{#if with_tests}}tested{{/if}}{{default module \"none\"}}"),
+                &MdmgCtx::new("foo")
+            )
+            .unwrap(),
+            "none"
+        );
+    }
+
     #[test]
     fn render_returning_helper() {
         assert_eq!(
@@ -194,6 +521,117 @@ mod tests {
         )
     }
 
+    #[test]
+    fn render_returning_string_helper() {
+        assert_eq!(
+            render(
+                Template::new("{{plural identify}} {{singular \"accounts\"}} {{upper identify}} {{lower identify}} {{screaming_snake_case identify}} {{title_case identify}} {{train_case identify}} {{replace identify \"Account\" \"User\"}} [{{trim \" foo \"}}]"), &MdmgCtx::new("exampleAccount")
+            )
+            .unwrap(),
+            "exampleAccounts account EXAMPLEACCOUNT exampleaccount EXAMPLE_ACCOUNT Example Account Example-Account exampleUser [foo]"
+        )
+    }
+
+    #[test]
+    fn render_returning_path_and_list_helper() {
+        let mut variables = Variables::new();
+        variables.insert("path".to_string(), json!("src/models/user.rs"));
+        variables.insert("tables".to_string(), json!(["users", "posts"]));
+        variables.insert("empty".to_string(), json!(""));
+
+        assert_eq!(
+            render(
+                Template::new("{{basename path}} {{dirname path}} {{join tables \", \"}} {{default empty \"none\"}} {{default missing \"none\"}} {{default identify \"none\"}}"),
+                &MdmgCtx::new("foo").with_variables(variables)
+            )
+            .unwrap(),
+            "user.rs src/models users, posts none none foo"
+        )
+    }
+
+    #[test]
+    fn render_returning_logical_helper() {
+        let mut variables = Variables::new();
+        variables.insert("with_tests".to_string(), json!(true));
+        variables.insert("with_docs".to_string(), json!(false));
+
+        assert_eq!(
+            render(
+                Template::new("{{#if (eq identify \"foo\")}}eq{{/if}} {{#if (ne identify \"foo\")}}ne{{/if}} {{#if (and with_tests with_docs)}}and{{/if}} {{#if (or with_tests with_docs)}}or{{/if}}"),
+                &MdmgCtx::new("foo").with_variables(variables)
+            )
+            .unwrap(),
+            "eq   or"
+        )
+    }
+
+    #[test]
+    fn render_returning_indent_helper() {
+        let mut variables = Variables::new();
+        variables.insert("body".to_string(), json!("foo\n\nbar\n"));
+
+        assert_eq!(
+            render(
+                Template::new("{{indent body 2}}"),
+                &MdmgCtx::new("foo").with_variables(variables)
+            )
+            .unwrap(),
+            "  foo\n\n  bar\n"
+        )
+    }
+
+    #[test]
+    fn render_returning_generated_value_helper() {
+        let actual = render(
+            Template::new("{{date \"%Y\"}}|{{now}}|{{uuid}}|{{uuid_v4}}"),
+            &MdmgCtx::new("foo"),
+        )
+        .unwrap();
+        let values = actual.split('|').collect::<Vec<&str>>();
+
+        assert_eq!(values[0], chrono::Local::now().format("%Y").to_string());
+        assert!(chrono::DateTime::parse_from_rfc3339(values[1]).is_ok());
+        assert!(uuid::Uuid::parse_str(values[2]).is_ok());
+        assert!(uuid::Uuid::parse_str(values[3]).is_ok());
+        assert_ne!(values[2], values[3]);
+    }
+
+    #[test]
+    fn render_returning_variables() {
+        let mut variables = Variables::new();
+        variables.insert("module".to_string(), json!("account"));
+        variables.insert("with_tests".to_string(), json!(true));
+        variables.insert("tables".to_string(), json!(["users", "posts"]));
+
+        assert_eq!(
+            render(
+                Template::new("{{module}}/{{identify}}{{#if with_tests}} tested{{/if}}{{#each tables}} {{this}}{{/each}}"),
+                &MdmgCtx::new("register").with_variables(variables)
+            )
+            .unwrap(),
+            "account/register tested users posts"
+        )
+    }
+
+    #[test]
+    fn render_returning_partials() {
+        let mut partials = Partials::new();
+        partials.insert(
+            "license_header".to_string(),
+            "// MIT {{identify}}\n".to_string(),
+        );
+
+        assert_eq!(
+            render(
+                Template::new("{{> license_header}}\nstruct {{pascal_case identify}};")
+                    .with_partials(partials),
+                &MdmgCtx::new("foo")
+            )
+            .unwrap(),
+            "// MIT foo\nstruct Foo;"
+        )
+    }
+
     #[test]
     fn render_returning_foo() {
         assert_eq!(
@@ -2,7 +2,8 @@ use itertools::{concat, Itertools};
 
 use crate::error::MdmgError;
 use crate::file::FileName;
-use crate::template::Template;
+use crate::project_root::{relative_path, working_dir};
+use crate::template::{Partials, Template};
 use crate::Result;
 
 use std::fs::{read_dir, read_to_string};
@@ -29,20 +30,23 @@ impl FSTemplateRepository {
         let xdg_dir = xdg::BaseDirectories::with_prefix("mdmg")
             .map(|x| x.list_data_files(""))
             .unwrap_or(vec![]);
-        xdg_dir.iter().fold(vec![], |acc, path| {
-            let file_name_opt: Option<FileName> = path
-                .file_stem()
-                .and_then(|name| name.to_str())
-                .and_then(|name| Some(FileName::new(name)));
-
-            match file_name_opt {
-                Some(file_name) => acc
-                    .into_iter()
-                    .chain(vec![file_name])
-                    .collect::<Vec<FileName>>(),
-                None => acc,
-            }
-        })
+        xdg_dir
+            .iter()
+            .filter(|path| path.is_file())
+            .fold(vec![], |acc, path| {
+                let file_name_opt: Option<FileName> = path
+                    .file_stem()
+                    .and_then(|name| name.to_str())
+                    .map(FileName::new);
+
+                match file_name_opt {
+                    Some(file_name) => acc
+                        .into_iter()
+                        .chain(vec![file_name])
+                        .collect::<Vec<FileName>>(),
+                    None => acc,
+                }
+            })
     }
 
     #[cfg(target_os = "windows")]
@@ -61,6 +65,35 @@ impl FSTemplateRepository {
     fn find_xdg_template_path(&self, name: String) -> Option<PathBuf> {
         None
     }
+
+    /// Partials in `partials` directories. Local partials override XDG partials with the same name.
+    fn partials(&self) -> Result<Partials> {
+        let directories = [
+            self.find_xdg_template_path("partials".to_string()),
+            Some(self.path.join("partials")),
+        ];
+        let mut partials = Partials::new();
+        for directory in directories.into_iter().flatten() {
+            partials.extend(read_partials(&directory)?);
+        }
+        Ok(partials)
+    }
+}
+
+fn read_partials(directory: &Path) -> Result<Partials> {
+    let entries = match read_dir(directory) {
+        Ok(entries) => entries.flatten(),
+        Err(_) => return Ok(Partials::new()),
+    };
+    entries
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            (!name.starts_with('.')).then_some((name, path))
+        })
+        .map(|(name, path)| Ok((name, read_to_string(&path)?)))
+        .collect()
 }
 
 impl TemplateRepository for FSTemplateRepository {
@@ -68,6 +101,8 @@ impl TemplateRepository for FSTemplateRepository {
         let local_dir = read_dir(&self.path)?.flatten();
 
         let local_dir_file_names = local_dir
+            .filter(|entry| entry.path().is_file())
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
             .map(|entry| {
                 let filename_result = entry.file_name().into_string();
                 filename_result
@@ -96,36 +131,57 @@ impl TemplateRepository for FSTemplateRepository {
             None
         };
         let xdg_data_dir_template_path = self.find_xdg_template_path(template_file_name);
-        let template_body = [local_template_path, xdg_data_dir_template_path]
+        let (template_path, body) = [local_template_path, xdg_data_dir_template_path]
             .into_iter()
-            .find_map(|s| s.map(|p| read_to_string(p).ok()))
-            .flatten();
+            .flatten()
+            .next()
+            .and_then(|path| read_to_string(&path).ok().map(|body| (path, body)))
+            .ok_or(MdmgError::TemplateIsNotFound(template_name))?;
 
-        let body = template_body.ok_or(MdmgError::TemplateIsNotFound(template_name))?;
+        // Errors point the plan relative to the directory mdmg is run in and count the trimmed lines.
+        let display_path = working_dir()
+            .map(|dir| relative_path(&template_path, &dir))
+            .unwrap_or_else(|_| template_path.clone());
+        let leading_lines = body[..body.len() - body.trim_start().len()]
+            .matches('\n')
+            .count();
 
-        Ok(Template::new(body.trim()))
+        Ok(Template::new(body.trim())
+            .with_partials(self.partials()?)
+            .with_origin(display_path.to_string_lossy(), leading_lines))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{FSTemplateRepository, FileName, TemplateRepository};
-    use crate::template::Template;
-    use std::env::{current_dir, set_var, var};
+    use crate::template::{render, MdmgCtx, Template};
+    use std::env::{current_dir, remove_var, set_var, var_os};
+    use std::ffi::OsString;
+
+    /// Restores `XDG_DATA_HOME` when dropped, even if the test panics.
+    struct XdgDataHome(Option<OsString>);
+
+    impl Drop for XdgDataHome {
+        fn drop(&mut self) {
+            match self.0.take() {
+                Some(xdg_data_dir) => set_var("XDG_DATA_HOME", xdg_data_dir),
+                None => remove_var("XDG_DATA_HOME"),
+            }
+        }
+    }
 
-    fn with_xdg_data_path<O: FnOnce() -> ()>(closure: O) {
-        let xdg_data_dir = var("XDG_DATA_HOME").unwrap_or("".to_string());
-        let current = current_dir().unwrap();
-        set_var("XDG_DATA_HOME", current.join("support/xdg_data_dir"));
+    fn with_xdg_data_path<O: FnOnce()>(path: &str, closure: O) {
+        let _restore = XdgDataHome(var_os("XDG_DATA_HOME"));
+        set_var("XDG_DATA_HOME", current_dir().unwrap().join(path));
         closure();
-        set_var("XDG_DATA_HOME", xdg_data_dir);
     }
 
     #[test]
     #[cfg_attr(not(feature = "fs-test"), ignore)]
     #[cfg(not(target_os = "windows"))]
     pub fn test_fstemplate_repository_list_return_to_files() {
-        with_xdg_data_path(|| {
+        with_xdg_data_path("support/xdg_data_dir", || {
             let repository =
                 FSTemplateRepository::new("./support/fs_template_repository_list_test");
             let result = repository.list().expect("result is error");
@@ -156,7 +212,11 @@ mod tests {
         let template = repository
             .resolve("foobar".to_string())
             .expect("template foobar is not found");
-        assert_eq!(template, Template::new("testing"));
+        assert_eq!(
+            template,
+            Template::new("testing")
+                .with_origin("./support/fs_template_repository_resolve_test/foobar.md", 0)
+        );
     }
 
     #[test]
@@ -164,13 +224,44 @@ mod tests {
     #[cfg(not(target_os = "windows"))]
     pub fn test_fstemplate_repository_resolve_return_to_template_when_selected_xdg_data_dir_templates(
     ) {
-        with_xdg_data_path(|| {
+        with_xdg_data_path("support/xdg_data_dir", || {
             let repository =
                 FSTemplateRepository::new("./support/fs_template_repository_resolve_test");
             let template = repository
                 .resolve("file4".to_string())
                 .expect("template foobar is not found");
-            assert_eq!(template, Template::new("xdg data dir"));
+            assert_eq!(
+                template,
+                Template::new("xdg data dir").with_origin("support/xdg_data_dir/mdmg/file4.md", 0)
+            );
+        })
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    #[cfg(not(target_os = "windows"))]
+    pub fn test_fstemplate_repository_resolve_return_to_template_with_partials() {
+        with_xdg_data_path("support/xdg_partials_data_dir", || {
+            let repository =
+                FSTemplateRepository::new("./support/fs_template_repository_partials_test");
+            let template = repository
+                .resolve("foobar".to_string())
+                .expect("template foobar is not found");
+            assert_eq!(
+                template
+                    .partials()
+                    .get("license_header")
+                    .map(String::as_str),
+                Some("// local license\n")
+            );
+            assert_eq!(
+                template.partials().get("footer").map(String::as_str),
+                Some("// xdg footer\n")
+            );
+            assert_eq!(
+                render(template, &MdmgCtx::new("foo")).unwrap(),
+                "## foo.rs\n\n```rust\n// local license\n// xdg footer\n```"
+            );
         })
     }
 }
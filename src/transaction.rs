@@ -0,0 +1,331 @@
+use crate::Result;
+
+use std::fs::{create_dir, read, remove_dir, remove_file, rename, write};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+#[cfg(unix)]
+use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+
+use tempfile::Builder;
+use yansi::Paint;
+
+/// File system operations that can be rolled back when a later step fails.
+pub trait Transaction {
+    fn create_dir_all(&self, path: &Path) -> Result<()>;
+    fn write(&self, path: &Path, body: &[u8]) -> Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+    fn remove_file(&self, path: &Path) -> Result<()>;
+    fn remove_dir(&self, path: &Path) -> Result<()>;
+    fn commit(&self);
+    fn rollback(&self) -> Result<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operation {
+    CreatedDirectory(PathBuf),
+    WroteFile {
+        path: PathBuf,
+        previous: Option<Vec<u8>>,
+    },
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    RemovedFile {
+        path: PathBuf,
+        body: Vec<u8>,
+    },
+    RemovedDirectory(PathBuf),
+}
+
+impl Operation {
+    fn revert(&self) -> Result<()> {
+        match self {
+            Operation::CreatedDirectory(path) => remove_dir(path)?,
+            Operation::WroteFile {
+                path,
+                previous: Some(previous),
+            } => write(path, previous)?,
+            Operation::WroteFile {
+                path,
+                previous: None,
+            } => remove_file(path)?,
+            Operation::Renamed { from, to } => rename(to, from)?,
+            Operation::RemovedFile { path, body } => write(path, body)?,
+            Operation::RemovedDirectory(path) => create_dir(path)?,
+        };
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FSTransaction {
+    operations: Mutex<Vec<Operation>>,
+}
+
+impl FSTransaction {
+    pub fn new() -> Self {
+        FSTransaction::default()
+    }
+
+    fn record(&self, operation: Operation) {
+        self.operations
+            .lock()
+            .expect("transaction is poisoned")
+            .push(operation);
+    }
+}
+
+fn parent_or_current(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+impl Transaction for FSTransaction {
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let mut missing = path
+            .ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .take_while(|ancestor| !ancestor.exists())
+            .collect::<Vec<&Path>>();
+        missing.reverse();
+        for directory in missing {
+            create_dir(directory)?;
+            self.record(Operation::CreatedDirectory(directory.to_path_buf()));
+        }
+        Ok(())
+    }
+
+    fn write(&self, path: &Path, body: &[u8]) -> Result<()> {
+        let previous = if path.exists() {
+            Some(read(path)?)
+        } else {
+            None
+        };
+        // Keep the file and its modification time when nothing changes.
+        if previous.as_deref() == Some(body) {
+            return Ok(());
+        }
+        // Write to a temporary file in the same directory and move it, so the file is never half written.
+        let mut builder = Builder::new();
+        #[cfg(unix)]
+        builder.permissions(Permissions::from_mode(0o666));
+        let mut file = builder.tempfile_in(parent_or_current(path))?;
+        if let Ok(metadata) = path.metadata() {
+            file.as_file().set_permissions(metadata.permissions())?;
+        }
+        file.write_all(body)?;
+        file.persist(path).map_err(|e| e.error)?;
+        self.record(Operation::WroteFile {
+            path: path.to_path_buf(),
+            previous,
+        });
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        rename(from, to)?;
+        self.record(Operation::Renamed {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        let body = read(path)?;
+        remove_file(path)?;
+        self.record(Operation::RemovedFile {
+            path: path.to_path_buf(),
+            body,
+        });
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        remove_dir(path)?;
+        self.record(Operation::RemovedDirectory(path.to_path_buf()));
+        Ok(())
+    }
+
+    fn commit(&self) {
+        self.operations
+            .lock()
+            .expect("transaction is poisoned")
+            .clear();
+    }
+
+    fn rollback(&self) -> Result<()> {
+        let operations =
+            std::mem::take(&mut *self.operations.lock().expect("transaction is poisoned"));
+        // Revert as many operations as possible and report the first failure.
+        let results = operations
+            .iter()
+            .rev()
+            .map(|operation| operation.revert())
+            .collect::<Vec<Result<()>>>();
+        results
+            .into_iter()
+            .find(|result| result.is_err())
+            .unwrap_or(Ok(()))
+    }
+}
+
+/// Commit the transaction when the closure succeeds, otherwise roll back every recorded operation.
+pub fn atomically<T, F: FnOnce() -> Result<T>>(transaction: &dyn Transaction, f: F) -> Result<T> {
+    match f() {
+        Ok(value) => {
+            transaction.commit();
+            Ok(value)
+        }
+        Err(e) => {
+            match transaction.rollback() {
+                Ok(_) => eprintln!("{} all changes", Paint::yellow("Rolled back:")),
+                Err(rollback_error) => {
+                    eprintln!("{} {}", Paint::red("Failed rollback:"), rollback_error)
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg(test)]
+mod tests {
+    use super::{atomically, FSTransaction, Transaction};
+    use crate::error::MdmgError;
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+    use std::path::Path;
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn fs_transaction_rollback_reverts_created_files_and_directories() {
+        let base = Path::new("./support/fs_transaction_rollback_reverts_created_files");
+        let transaction = FSTransaction::new();
+
+        assert!(transaction.create_dir_all(&base.join("foo/bar")).is_ok());
+        assert!(transaction
+            .write(&base.join("foo/bar/baz.md"), b"dummy")
+            .is_ok());
+        assert!(base.join("foo/bar/baz.md").exists());
+
+        assert!(transaction.rollback().is_ok());
+        assert!(!base.exists());
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn fs_transaction_rollback_restores_overwritten_renamed_and_removed_files() {
+        let base = Path::new("./support/fs_transaction_rollback_restores_files");
+        assert!(create_dir_all(base.join("dir")).is_ok());
+        assert!(write(base.join("overwritten.md"), "before").is_ok());
+        assert!(write(base.join("renamed.md"), "renamed").is_ok());
+        assert!(write(base.join("dir/removed.md"), "removed").is_ok());
+
+        let transaction = FSTransaction::new();
+        assert!(transaction
+            .write(&base.join("overwritten.md"), b"after")
+            .is_ok());
+        assert!(transaction
+            .rename(&base.join("renamed.md"), &base.join("renamed2.md"))
+            .is_ok());
+        assert!(transaction
+            .remove_file(&base.join("dir/removed.md"))
+            .is_ok());
+        assert!(transaction.remove_dir(&base.join("dir")).is_ok());
+
+        assert!(transaction.rollback().is_ok());
+        assert_eq!(
+            read_to_string(base.join("overwritten.md")).unwrap(),
+            "before"
+        );
+        assert_eq!(read_to_string(base.join("renamed.md")).unwrap(), "renamed");
+        assert!(!base.join("renamed2.md").exists());
+        assert_eq!(
+            read_to_string(base.join("dir/removed.md")).unwrap(),
+            "removed"
+        );
+        remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn fs_transaction_commit_keeps_changes() {
+        let base = Path::new("./support/fs_transaction_commit_keeps_changes");
+        let transaction = FSTransaction::new();
+
+        assert!(transaction.create_dir_all(base).is_ok());
+        assert!(transaction.write(&base.join("foo.md"), b"dummy").is_ok());
+        transaction.commit();
+
+        assert!(transaction.rollback().is_ok());
+        assert_eq!(read_to_string(base.join("foo.md")).unwrap(), "dummy");
+        remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn atomically_rolls_back_when_failed() {
+        let base = Path::new("./support/atomically_rolls_back_when_failed");
+        let transaction = FSTransaction::new();
+
+        let actual: crate::Result<()> = atomically(&transaction, || {
+            transaction.create_dir_all(base)?;
+            transaction.write(&base.join("foo.md"), b"dummy")?;
+            Err(MdmgError::ApplicationError)
+        });
+
+        assert!(actual.is_err());
+        assert!(!base.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn fs_transaction_write_keeps_permissions_and_identical_files() {
+        use std::fs::{metadata, set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+
+        let base = Path::new("./support/fs_transaction_write_keeps_permissions");
+        assert!(create_dir_all(base).is_ok());
+        assert!(write(base.join("script.sh"), "echo foo").is_ok());
+        assert!(set_permissions(base.join("script.sh"), Permissions::from_mode(0o755)).is_ok());
+        let modified = metadata(base.join("script.sh"))
+            .unwrap()
+            .modified()
+            .unwrap();
+
+        let transaction = FSTransaction::new();
+        assert!(transaction
+            .write(&base.join("script.sh"), b"echo foo")
+            .is_ok());
+        assert_eq!(
+            metadata(base.join("script.sh"))
+                .unwrap()
+                .modified()
+                .unwrap(),
+            modified
+        );
+        assert!(transaction
+            .write(&base.join("script.sh"), b"echo bar")
+            .is_ok());
+        assert_eq!(
+            metadata(base.join("script.sh"))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777,
+            0o755
+        );
+        assert!(transaction.write(&base.join("new.md"), b"new").is_ok());
+        assert_ne!(
+            metadata(base.join("new.md")).unwrap().permissions().mode() & 0o077,
+            0
+        );
+        remove_dir_all(base).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+use crate::error::MdmgError;
+use crate::Result;
+
+use serde_json::{Map, Value};
+use std::fs::read_to_string;
+use std::path::Path;
+
+pub type Variables = Map<String, Value>;
+
+const RESERVED_VARIABLE_NAMES: [&str; 1] = ["identify"];
+
+pub fn parse_variable(input: &str) -> Result<(String, Value)> {
+    let (key, value) = input
+        .split_once('=')
+        .ok_or_else(|| MdmgError::InvalidVariable(input.to_string()))?;
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(MdmgError::InvalidVariable(input.to_string()));
+    }
+    if RESERVED_VARIABLE_NAMES.contains(&key) {
+        return Err(MdmgError::ReservedVariable(key.to_string()));
+    }
+    Ok((key.to_string(), parse_value(value)))
+}
+
+fn parse_value(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+}
+
+pub fn load_variables_file(path: &Path) -> Result<Variables> {
+    let body = read_to_string(path)?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    let to_parse_error = |reason: String| MdmgError::VariablesFileParseError {
+        path: path.to_string_lossy().to_string(),
+        reason,
+    };
+
+    let value: Value = match extension {
+        "json" => serde_json::from_str(&body).map_err(|e| to_parse_error(e.to_string()))?,
+        "yaml" | "yml" => serde_yaml::from_str(&body).map_err(|e| to_parse_error(e.to_string()))?,
+        "toml" => toml::from_str(&body).map_err(|e| to_parse_error(e.to_string()))?,
+        _ => return Err(to_parse_error("unsupported file extension".to_string())),
+    };
+
+    let variables = match value {
+        Value::Object(variables) => variables,
+        _ => return Err(to_parse_error("top level must be a table".to_string())),
+    };
+
+    match variables
+        .keys()
+        .find(|key| RESERVED_VARIABLE_NAMES.contains(&key.as_str()))
+    {
+        Some(key) => Err(MdmgError::ReservedVariable(key.clone())),
+        None => Ok(variables),
+    }
+}
+
+/// Parse a dotenv file of `KEY=VALUE` lines. Blank lines, `#` comments and `export` prefixes are allowed.
+pub fn load_env_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let body = read_to_string(path)?;
+    parse_env(&body).map_err(|reason| MdmgError::EnvFileParseError {
+        path: path.to_string_lossy().to_string(),
+        reason,
+    })
+}
+
+fn parse_env(body: &str) -> std::result::Result<Vec<(String, String)>, String> {
+    body.lines()
+        .enumerate()
+        .map(|(index, line)| (index, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(index, line)| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line
+                .split_once('=')
+                .filter(|(key, _)| !key.trim().is_empty())
+                .ok_or_else(|| format!("line {} is not KEY=VALUE", index + 1))?;
+            let value = value.trim();
+            let value = [('"', '"'), ('\'', '\'')]
+                .iter()
+                .find_map(|(open, close)| {
+                    value
+                        .strip_prefix(*open)
+                        .and_then(|value| value.strip_suffix(*close))
+                })
+                .unwrap_or(value);
+            Ok((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+pub fn merge_variables(base: Variables, overrides: Vec<(String, Value)>) -> Variables {
+    overrides.into_iter().fold(base, |mut acc, (key, value)| {
+        acc.insert(key, value);
+        acc
+    })
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg(test)]
+mod tests {
+    use super::{load_variables_file, merge_variables, parse_env, parse_variable, Variables};
+    use serde_json::{json, Value};
+    use std::path::Path;
+
+    #[test]
+    fn parse_variable_returning_typed_values() {
+        assert_eq!(
+            parse_variable("module=foo").unwrap(),
+            ("module".to_string(), json!("foo"))
+        );
+        assert_eq!(
+            parse_variable("count=3").unwrap(),
+            ("count".to_string(), json!(3))
+        );
+        assert_eq!(
+            parse_variable("with_tests=true").unwrap(),
+            ("with_tests".to_string(), json!(true))
+        );
+        assert_eq!(
+            parse_variable("tables=[\"users\", \"posts\"]").unwrap(),
+            ("tables".to_string(), json!(["users", "posts"]))
+        );
+        assert_eq!(
+            parse_variable("title=a=b").unwrap(),
+            ("title".to_string(), json!("a=b"))
+        );
+        assert_eq!(
+            parse_variable("empty=").unwrap(),
+            ("empty".to_string(), json!(""))
+        );
+    }
+
+    #[test]
+    fn parse_variable_is_error_when_invalid_format() {
+        assert!(parse_variable("module").is_err());
+        assert!(parse_variable("=foo").is_err());
+        assert!(parse_variable("identify=foo").is_err());
+    }
+
+    #[test]
+    fn parse_env_returning_pairs() {
+        let body = "# comment\nFOO=foo\n\nexport BAR = \"bar baz\"\nQUX='a=b'\nEMPTY=\n";
+        assert_eq!(
+            parse_env(body).unwrap(),
+            vec![
+                ("FOO".to_string(), "foo".to_string()),
+                ("BAR".to_string(), "bar baz".to_string()),
+                ("QUX".to_string(), "a=b".to_string()),
+                ("EMPTY".to_string(), "".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_env("FOO=foo\nBAR\n").unwrap_err(),
+            "line 2 is not KEY=VALUE"
+        );
+    }
+
+    #[test]
+    fn merge_variables_overrides_base() {
+        let mut base = Variables::new();
+        base.insert("module".to_string(), json!("foo"));
+        base.insert("author".to_string(), json!("himanoa"));
+
+        let merged = merge_variables(base, vec![("module".to_string(), json!("bar"))]);
+
+        assert_eq!(merged.get("module"), Some(&json!("bar")));
+        assert_eq!(merged.get("author"), Some(&json!("himanoa")));
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn load_variables_file_returning_variables() {
+        for file_name in ["vars.json", "vars.yaml", "vars.toml"] {
+            let path = Path::new("./support/variable_load_variables_file_test").join(file_name);
+            let variables = load_variables_file(&path).expect("failed load variables file");
+            assert_eq!(
+                Value::Object(variables),
+                json!({ "module": "user", "with_tests": true, "tables": ["users", "posts"] }),
+                "{}",
+                file_name
+            );
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "fs-test"), ignore)]
+    fn load_variables_file_is_error_when_unsupported_extension() {
+        let path = Path::new("./support/variable_load_variables_file_test/vars.txt");
+        assert!(load_variables_file(path).is_err());
+    }
+}